@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use http::http::{borrowed, Request, RequestGenerator};
+use std::convert::TryFrom;
+
+const BATCH: usize = 1000;
+
+fn request_parsing(c: &mut Criterion) {
+    let requests: Vec<Vec<u8>> = RequestGenerator::new(0x5eed).take(BATCH).collect();
+    let bytes: usize = requests.iter().map(Vec::len).sum();
+
+    let mut group = c.benchmark_group("request_parsing");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            for request in &requests {
+                black_box(Request::try_from(&request[..]).unwrap());
+            }
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            for request in &requests {
+                black_box(borrowed::Request::parse(request).unwrap());
+            }
+        })
+    });
+    group.bench_function("borrowed_into_owned", |b| {
+        b.iter(|| {
+            for request in &requests {
+                black_box(borrowed::Request::parse(request).unwrap().into_owned());
+            }
+        })
+    });
+    group.finish();
+
+    // Headers dominate real requests, so show how each parser scales with them.
+    let mut group = c.benchmark_group("request_parsing_headers");
+    for count in [1, 5, 10] {
+        let request = RequestGenerator::new(count as u64)
+            .max_headers(count)
+            .max_body_size(0)
+            .next()
+            .unwrap();
+        group.bench_function(format!("owned/{}", count), |b| {
+            b.iter_batched(
+                || request.clone(),
+                |request| Request::try_from(&request[..]).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(format!("borrowed/{}", count), |b| {
+            b.iter(|| black_box(borrowed::Request::parse(black_box(&request)).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, request_parsing);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+#![no_main]
+
+// cargo +nightly fuzz run request ../tests/corpus/request
+// Copy any crash found into tests/corpus/request as a regression case.
+
+use http::http::Request;
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(request) = Request::try_from(data) {
+        // Whatever parses must serialize to something that parses the same way.
+        let bytes = request.to_bytes();
+        let reparsed = Request::try_from(&bytes[..]).expect("serialized request must parse");
+        assert_eq!(reparsed.method, request.method);
+        assert_eq!(reparsed.path, request.path);
+        assert_eq!(reparsed.query_string, request.query_string);
+        assert_eq!(reparsed.body, request.body);
+    }
+});
@@ -0,0 +1,65 @@
+use crate::config::Config;
+use crate::http::{Request, Response, StatusCode};
+use crate::metrics::Metrics;
+use crate::router::Router;
+use crate::server::{Handler, ServerHandle};
+
+pub fn healthz(_: &mut Request) -> Response {
+    Response::new(StatusCode::OK).with_body("ok")
+}
+
+// Fails once the server starts draining so load balancers stop sending new requests.
+pub struct Readiness {
+    server: ServerHandle,
+}
+
+impl Readiness {
+    pub fn new(server: ServerHandle) -> Self {
+        Readiness { server }
+    }
+}
+
+impl Handler for Readiness {
+    fn handle_request(&self, _request: &mut Request) -> Response {
+        if self.server.is_draining() {
+            Response::new(StatusCode::SERVICE_UNAVAILABLE).with_body("draining")
+        } else {
+            Response::new(StatusCode::OK).with_body("ready")
+        }
+    }
+}
+
+// Routes for the admin listener, which should only be reachable by operators. Metrics are
+// served at the configured `metrics_path`.
+pub fn router(config: Config, server: ServerHandle, metrics: Option<Metrics>) -> Router {
+    let connections = server.clone();
+    let drain = server.clone();
+
+    let mut router = Router::new();
+    if let (Some(path), Some(metrics)) = (&config.metrics_path, metrics) {
+        router = router.get(path, metrics);
+    }
+    router
+        .get("/healthz", healthz)
+        .get("/readyz", Readiness::new(server))
+        .get("/config", move |_: &mut Request| json(&config))
+        .get("/connections", move |_: &mut Request| {
+            json(&serde_json::json!({
+                "active": connections.active_connections(),
+                "draining": connections.is_draining(),
+            }))
+        })
+        .post("/drain", move |_: &mut Request| {
+            drain.shutdown();
+            Response::new(StatusCode::ACCEPTED).with_body("draining")
+        })
+}
+
+fn json(value: &impl serde::Serialize) -> Response {
+    match serde_json::to_vec_pretty(value) {
+        Ok(body) => Response::new(StatusCode::OK)
+            .with_header("Content-Type", "application/json")
+            .with_body(body),
+        Err(e) => Response::new(StatusCode::INTERNAL_SERVER_ERROR).with_body(e.to_string()),
+    }
+}
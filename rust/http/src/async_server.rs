@@ -0,0 +1,351 @@
+use crate::http::{ParseError, Request, Response, StatusCode};
+use crate::metrics::Metrics;
+use crate::server::{
+    body_length, check_expectation, line_limit, on_head_line, parse_request, ConnectionSettings,
+    Exchange, Handler, HeadLine, ReadError, Server, CONTINUE,
+};
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{self, Handle};
+use tokio::task::{self, JoinSet};
+use tokio::time;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// Handlers for `Server::run_async`. Every blocking `Handler`, `Router` included, is one
+// too, so the same application runs under either server.
+pub trait AsyncHandler: Send + Sync {
+    fn handle_request<'a>(&'a self, request: &'a mut Request) -> BoxFuture<'a, Response>;
+
+    // See `Handler::check_head`; it runs on the runtime, so it must not block.
+    fn check_head(&self, _request: &mut Request) -> Option<Response> {
+        None
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        println!("Failed to parse a request: {}", e);
+        let status = e.status_code();
+        Response::new(status).with_body(status.reason_phrase().to_string())
+    }
+}
+
+impl<H: Handler> AsyncHandler for H {
+    fn handle_request<'a>(&'a self, request: &'a mut Request) -> BoxFuture<'a, Response> {
+        // Blocking handlers may sleep or do I/O, so keep them off the other tasks' workers.
+        Box::pin(async move { task::block_in_place(|| Handler::handle_request(self, request)) })
+    }
+
+    fn check_head(&self, request: &mut Request) -> Option<Response> {
+        Handler::check_head(self, request)
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        Handler::handle_bad_request(self, e)
+    }
+}
+
+// Wraps an async closure, e.g. `async_fn(|request| Box::pin(async move { ... }))`.
+pub struct AsyncFn<F>(F);
+
+pub fn async_fn<F>(f: F) -> AsyncFn<F>
+where
+    F: for<'a> Fn(&'a mut Request) -> BoxFuture<'a, Response> + Send + Sync,
+{
+    AsyncFn(f)
+}
+
+impl<F> AsyncHandler for AsyncFn<F>
+where
+    F: for<'a> Fn(&'a mut Request) -> BoxFuture<'a, Response> + Send + Sync,
+{
+    fn handle_request<'a>(&'a self, request: &'a mut Request) -> BoxFuture<'a, Response> {
+        (self.0)(request)
+    }
+}
+
+impl Server {
+    // Serves on a multi-threaded tokio runtime; `workers` sets its thread count.
+    pub fn run_async(&mut self, handler: impl AsyncHandler + 'static) -> IoResult<()> {
+        let mut builder = runtime::Builder::new_multi_thread();
+        if let Some(workers) = self.workers {
+            builder.worker_threads(workers);
+        }
+        builder.enable_all().build()?.block_on(self.serve(handler))
+    }
+
+    // Must run on a multi-threaded runtime, since blocking handlers use `block_in_place`.
+    pub async fn serve(&mut self, handler: impl AsyncHandler + 'static) -> IoResult<()> {
+        #[cfg(feature = "tls")]
+        if self.settings.tls.is_some() {
+            return Err(IoError::new(
+                ErrorKind::Unsupported,
+                "TLS is only supported by the threaded server",
+            ));
+        }
+        let mut addresses = vec![format!("{}:{}", self.ip_address, self.port)];
+        addresses.extend(self.extra_addresses.iter().cloned());
+
+        let state = Arc::clone(&self.settings.state);
+        let mut listeners = Vec::new();
+        for address in &addresses {
+            let listener = TcpListener::bind(address).await?;
+            state.addresses.lock().unwrap().push(listener.local_addr()?);
+            listeners.push(listener);
+            println!("Server is running on {} (async)", address);
+        }
+        if state.draining.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let handler: Arc<dyn AsyncHandler> = Arc::new(handler);
+        let connections = state
+            .connections
+            .with_limits(self.max_connections, self.max_connections_per_ip);
+
+        let mut accept_tasks = JoinSet::new();
+        for listener in listeners {
+            let handler = Arc::clone(&handler);
+            let connections = connections.clone();
+            let settings = self.settings.clone();
+            accept_tasks.spawn(async move {
+                loop {
+                    let accepted = listener.accept().await;
+                    if settings.state.draining.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let (mut stream, addr) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            println!("Failed to establish a connection: {}", e);
+                            continue;
+                        }
+                    };
+                    let guard = match connections.try_acquire(addr.ip()) {
+                        Some(guard) => guard,
+                        None => {
+                            println!("Connection limit reached, rejecting {}", addr);
+                            let response = Response::new(StatusCode::SERVICE_UNAVAILABLE)
+                                .with_header("Connection", "close")
+                                .with_header("Retry-After", "1")
+                                .with_body("Too many connections");
+                            let mut bytes = Vec::new();
+                            if response.send(&mut bytes).is_ok() {
+                                let _ = stream.write_all(&bytes).await;
+                            }
+                            continue;
+                        }
+                    };
+
+                    let connection_metric =
+                        settings.metrics.as_ref().map(Metrics::connection_opened);
+                    let handler = Arc::clone(&handler);
+                    let settings = settings.clone();
+                    tokio::spawn(async move {
+                        let _guard = guard;
+                        let _connection_metric = connection_metric;
+                        handle_connection(stream, addr, handler.as_ref(), &settings).await;
+                    });
+                }
+            });
+        }
+        while accept_tasks.join_next().await.is_some() {}
+
+        println!("Draining {} connection(s)", connections.active());
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while connections.active() > 0 && Instant::now() < deadline {
+            time::sleep(Duration::from_millis(50)).await;
+        }
+        if connections.active() > 0 {
+            println!(
+                "Shutdown timeout reached with {} connection(s) still open",
+                connections.active()
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    handler: &dyn AsyncHandler,
+    settings: &ConnectionSettings,
+) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    loop {
+        let read = time::timeout(
+            settings.keep_alive_timeout,
+            read_request(
+                &mut reader,
+                &mut write_half,
+                settings.max_body_size,
+                |head| check_expectation(head, addr, |request| handler.check_head(request)),
+            ),
+        )
+        .await;
+        let (response, exchange) = match read {
+            Err(_) | Ok(Ok(None)) => break,
+            Ok(Ok(Some(buffer))) => {
+                let started = Instant::now();
+                match parse_request(&buffer, addr, settings) {
+                    Ok(mut request) => {
+                        let response = handler.handle_request(&mut request).await;
+                        Exchange::new(Some(&request), response, addr, settings, started)
+                    }
+                    Err(e) => Exchange::new(
+                        None,
+                        handler.handle_bad_request(&e),
+                        addr,
+                        settings,
+                        started,
+                    ),
+                }
+            }
+            Ok(Err(ReadError::Io(e))) => {
+                println!("Failed to read from connection: {}", e);
+                break;
+            }
+            Ok(Err(ReadError::Rejected(rejected))) => {
+                let (request, response) = *rejected;
+                let response = response.with_header("Connection", "close");
+                Exchange::new(Some(&request), response, addr, settings, Instant::now())
+            }
+            Ok(Err(e)) => {
+                let response = e.into_response().with_header("Connection", "close");
+                if let Err(e) = write_response(&mut write_half, response, false, settings).await {
+                    println!("Failed to send response: {}", e);
+                }
+                break;
+            }
+        };
+
+        let result = write_response(&mut write_half, response, exchange.is_head, settings).await;
+        let written = *result.as_ref().unwrap_or(&0);
+        let (keep_alive, upgrade) = exchange.finish(settings, written);
+        if let Err(e) = result {
+            println!("Failed to send response: {}", e);
+            break;
+        }
+
+        if let Some(upgrade) = upgrade {
+            // Upgraded protocols speak blocking std sockets, so hand the connection over.
+            if !reader.buffer().is_empty() {
+                println!("Dropping upgrade with bytes already buffered from {}", addr);
+                break;
+            }
+            let stream = match reader.into_inner().reunite(write_half) {
+                Ok(stream) => stream,
+                Err(_) => break,
+            };
+            let converted = stream.into_std().and_then(|stream| {
+                stream.set_nonblocking(false)?;
+                Ok((std::io::BufReader::new(stream.try_clone()?), stream))
+            });
+            match converted {
+                Ok((reader, stream)) => {
+                    let _ = task::spawn_blocking(move || upgrade.run(reader, stream)).await;
+                }
+                Err(e) => println!("Failed to hand over upgraded connection: {}", e),
+            }
+            return;
+        }
+        if !keep_alive {
+            break;
+        }
+    }
+}
+
+// In-memory bodies are written asynchronously; streamed ones are `Read`ers, so they are
+// copied on a blocking section of the runtime.
+async fn write_response(
+    stream: &mut OwnedWriteHalf,
+    response: Response,
+    is_head: bool,
+    settings: &ConnectionSettings,
+) -> IoResult<u64> {
+    if response.body().is_none() && !is_head {
+        return task::block_in_place(|| {
+            let mut writer = BlockingWriter {
+                stream,
+                handle: Handle::current(),
+                written: 0,
+            };
+            response.send(&mut writer).map(|_| writer.written)
+        });
+    }
+
+    let mut bytes = Vec::new();
+    if is_head {
+        response.send_head(&mut bytes)?;
+    } else {
+        response.send(&mut bytes)?;
+    }
+    let write = stream.write_all(&bytes);
+    match settings.write_timeout {
+        Some(timeout) => time::timeout(timeout, write)
+            .await
+            .map_err(|_| IoError::new(ErrorKind::TimedOut, "write timed out"))??,
+        None => write.await?,
+    }
+    Ok(bytes.len() as u64)
+}
+
+struct BlockingWriter<'a> {
+    stream: &'a mut OwnedWriteHalf,
+    handle: Handle,
+    written: u64,
+}
+
+impl Write for BlockingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let written = self.handle.block_on(self.stream.write(buf))?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.handle.block_on(self.stream.flush())
+    }
+}
+
+// `server::read_request` over tokio streams, with the same framing rules.
+async fn read_request(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    writer: &mut OwnedWriteHalf,
+    max_body_size: usize,
+    check_head: impl FnOnce(&[u8]) -> Result<bool, ReadError>,
+) -> Result<Option<Vec<u8>>, ReadError> {
+    let mut buffer = Vec::new();
+    loop {
+        let read = (&mut *reader)
+            .take(line_limit(&buffer))
+            .read_until(b'\n', &mut buffer)
+            .await?;
+        match on_head_line(&mut buffer, read)? {
+            HeadLine::Incomplete => {}
+            HeadLine::Complete => break,
+            HeadLine::Closed => return Ok(None),
+        }
+    }
+
+    let content_length = body_length(&buffer, max_body_size)?;
+    if check_head(&buffer)? && content_length > 0 {
+        writer.write_all(CONTINUE).await?;
+    }
+    if content_length > 0 {
+        let head_length = buffer.len();
+        buffer.resize(head_length + content_length, 0);
+        reader.read_exact(&mut buffer[head_length..]).await?;
+    }
+    Ok(Some(buffer))
+}
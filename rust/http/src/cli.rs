@@ -0,0 +1,151 @@
+use http::config::SETTINGS;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: http [OPTIONS]
+       http replay <FILE.har> --target <URL> [--ignore-header <NAME>]...
+
+Options:
+  -c, --config <FILE>            TOML config file (or HTTP_CONFIG)
+      --listen <ADDR>            Address to listen on, repeatable
+      --workers <N>              Serve connections from N worker threads
+      --keep-alive-timeout <S>   Idle seconds before a connection is closed
+      --write-timeout <S>        Seconds allowed for a single write
+      --max-body-size <BYTES>    Largest accepted request body
+      --max-connections <N>      Open connections across all clients
+      --max-connections-per-ip <N>
+      --log-format <text|json>   Access log format
+      --static-root <DIR>        Directory served at /
+      --metrics-path <PATH>      Serve Prometheus metrics at PATH on the admin address
+      --health-checks <BOOL>     Serve /healthz and /readyz
+      --shutdown-timeout <S>     Seconds to wait for connections when draining
+      --admin-listen <ADDR>      Serve admin routes on ADDR, e.g. 127.0.0.1:9090
+      --runtime <threads|async>  Blocking threads or the tokio runtime
+      --tls-cert <FILE>          PEM certificate chain; serves HTTPS with --tls-key
+      --tls-key <FILE>           PEM private key
+      --https-redirect <ADDR>    Redirect plain HTTP on ADDR to HTTPS
+      --record-har <FILE>        Record every exchange to a HAR file
+      --rate-limit <N>           Requests per second per client address, 0 for none
+      --rate-limit-burst <N>     Requests a client may make at once
+      --check-config             Validate the configuration and exit
+  -h, --help                     Print this help
+
+Settings are read from the config file, then HTTP_<SETTING> environment
+variables, then flags.
+
+`replay` re-sends the requests in a HAR file to --target, e.g.
+http://127.0.0.1:8080, and reports responses that differ from the recording.
+It exits with 1 when any response differs or fails.";
+
+pub enum Command {
+    Serve(Options),
+    Replay(ReplayOptions),
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        match args.split_first() {
+            Some((command, rest)) if command == "replay" => {
+                ReplayOptions::parse(rest).map(Command::Replay)
+            }
+            _ => Options::parse(args).map(Command::Serve),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplayOptions {
+    pub har: PathBuf,
+    pub target: String,
+    pub ignored_headers: Vec<String>,
+}
+
+impl ReplayOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut har = None;
+        let mut target = None;
+        let mut ignored_headers = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |flag: &str| match &inline_value {
+                Some(value) => Ok(value.clone()),
+                None => args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {}", flag)),
+            };
+
+            match flag {
+                "--target" => target = Some(value(flag)?),
+                "--ignore-header" => ignored_headers.push(value(flag)?),
+                _ if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+                _ if har.is_none() => har = Some(PathBuf::from(flag)),
+                _ => return Err(format!("unexpected argument {}", flag)),
+            }
+        }
+
+        Ok(ReplayOptions {
+            har: har.ok_or("replay needs a HAR file")?,
+            target: target.ok_or("replay needs --target")?,
+            ignored_headers,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub config: Option<PathBuf>,
+    pub overrides: Vec<(String, String)>,
+    pub check_config: bool,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut listen: Vec<String> = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |flag: &str| match &inline_value {
+                Some(value) => Ok(value.clone()),
+                None => args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {}", flag)),
+            };
+
+            match flag {
+                "-h" | "--help" => options.help = true,
+                "--check-config" => options.check_config = true,
+                "-c" | "--config" => options.config = Some(PathBuf::from(value(flag)?)),
+                "--listen" => listen.push(value(flag)?),
+                _ => {
+                    let setting = flag
+                        .strip_prefix("--")
+                        .map(|name| name.replace('-', "_"))
+                        .filter(|name| SETTINGS.contains(&name.as_str()))
+                        .ok_or_else(|| format!("unknown option {}", flag))?;
+                    let value = value(flag)?;
+                    options.overrides.push((setting, value));
+                }
+            }
+        }
+
+        if !listen.is_empty() {
+            options
+                .overrides
+                .push((String::from("listen"), listen.join(",")));
+        }
+        Ok(options)
+    }
+}
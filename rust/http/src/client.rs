@@ -0,0 +1,377 @@
+use crate::http::chunked::ChunkedReader;
+use crate::http::response::read_final_head;
+use crate::http::{Framing, Headers, Method, Response, StatusCode};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{BufReader, Error as IoError, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MAX_IDLE_PER_HOST: usize = 4;
+
+#[derive(Debug)]
+pub enum ClientError {
+    InvalidUrl(String),
+    TooManyRedirects,
+    Io(IoError),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
+            Self::TooManyRedirects => write!(f, "Too many redirects"),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<IoError> for ClientError {
+    fn from(e: IoError) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    // Path including the query string.
+    pub target: String,
+}
+
+impl Url {
+    // Only plain `http://host[:port][/path][?query]` URLs are supported.
+    pub fn parse(url: &str) -> Result<Self, ClientError> {
+        let invalid = || ClientError::InvalidUrl(url.to_string());
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_string()),
+            None => (rest, String::from("/")),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let target = match target.split_once('#') {
+            Some((target, _)) => target.to_string(),
+            None => target,
+        };
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            target,
+        })
+    }
+
+    fn authority(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    // Resolves a `Location` header, which may be absolute or relative to this URL.
+    fn join(&self, location: &str) -> Result<Url, ClientError> {
+        if location.starts_with("http://") {
+            Url::parse(location)
+        } else if location.starts_with('/') {
+            Ok(Url {
+                target: location.to_string(),
+                ..self.clone()
+            })
+        } else {
+            let path = self.target.split('?').next().unwrap_or("/");
+            let base = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+            Ok(Url {
+                target: format!("{}{}", base, location),
+                ..self.clone()
+            })
+        }
+    }
+}
+
+pub struct Client {
+    connect_timeout: Duration,
+    timeout: Duration,
+    max_redirects: usize,
+    idle: Mutex<HashMap<String, Vec<BufReader<TcpStream>>>>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Client {
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            max_redirects: 10,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    // Applies to every read and write on the connection.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Zero disables following redirects.
+    pub fn max_redirects(mut self, limit: usize) -> Self {
+        self.max_redirects = limit;
+        self
+    }
+
+    pub fn get(&self, url: &str) -> Result<Response, ClientError> {
+        self.request(Method::GET, url).send()
+    }
+
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder<'_> {
+        RequestBuilder {
+            client: self,
+            method,
+            url: url.to_string(),
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn execute(
+        &self,
+        mut method: Method,
+        url: &str,
+        mut headers: Headers,
+        mut body: Vec<u8>,
+    ) -> Result<Response, ClientError> {
+        let mut url = Url::parse(url)?;
+        let mut redirects = 0;
+        loop {
+            let response = self.round_trip(&method, &url, &headers, &body)?;
+            let status_code = response.status_code();
+            let location = response.headers.get("Location");
+            let location = match location {
+                Some(location) if status_code.is_redirection() => location.to_string(),
+                _ => return Ok(response),
+            };
+            if self.max_redirects == 0 {
+                return Ok(response);
+            }
+            if redirects == self.max_redirects {
+                return Err(ClientError::TooManyRedirects);
+            }
+            redirects += 1;
+
+            let next = url.join(&location)?;
+            if next.host != url.host || next.port != url.port {
+                headers.remove("Authorization");
+                headers.remove("Cookie");
+            }
+            let keeps_method = status_code == StatusCode::TEMPORARY_REDIRECT
+                || status_code == StatusCode::PERMANENT_REDIRECT;
+            if !keeps_method && method != Method::HEAD {
+                method = Method::GET;
+                body.clear();
+                headers.remove("Content-Type");
+            }
+            url = next;
+        }
+    }
+
+    fn round_trip(
+        &self,
+        method: &Method,
+        url: &Url,
+        headers: &Headers,
+        body: &[u8],
+    ) -> Result<Response, ClientError> {
+        let head = request_head(method, url, headers, body.len());
+
+        // A pooled connection may have been closed by the server while idle, so a failure
+        // before any response bytes arrive is retried once on a fresh connection. Only for
+        // idempotent methods: the server may have acted on a POST it never answered.
+        if let Some(mut connection) = self.take_idle(url) {
+            match exchange(&mut connection, method, &head, body) {
+                Ok((response, reusable)) => {
+                    if reusable {
+                        self.put_idle(url, connection);
+                    }
+                    return Ok(response);
+                }
+                Err(Exchange::Stale) if method.is_idempotent() => {}
+                Err(Exchange::Stale) => return Err(closed_early()),
+                Err(Exchange::Failed(e)) => return Err(e.into()),
+            }
+        }
+
+        let mut connection = BufReader::new(self.connect(url)?);
+        match exchange(&mut connection, method, &head, body) {
+            Ok((response, reusable)) => {
+                if reusable {
+                    self.put_idle(url, connection);
+                }
+                Ok(response)
+            }
+            Err(Exchange::Stale) => Err(closed_early()),
+            Err(Exchange::Failed(e)) => Err(e.into()),
+        }
+    }
+
+    fn connect(&self, url: &Url) -> Result<TcpStream, ClientError> {
+        let mut last_error = None;
+        for addr in url.address().to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(match last_error {
+            Some(e) => e.into(),
+            None => ClientError::InvalidUrl(url.address()),
+        })
+    }
+
+    fn take_idle(&self, url: &Url) -> Option<BufReader<TcpStream>> {
+        self.idle
+            .lock()
+            .unwrap()
+            .get_mut(&url.address())
+            .and_then(|connections| connections.pop())
+    }
+
+    fn put_idle(&self, url: &Url, connection: BufReader<TcpStream>) {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(url.address()).or_default();
+        if connections.len() < MAX_IDLE_PER_HOST {
+            connections.push(connection);
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RequestBuilder<'a> {
+    client: &'a Client,
+    method: Method,
+    url: String,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl RequestBuilder<'_> {
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn send(self) -> Result<Response, ClientError> {
+        self.client
+            .execute(self.method, &self.url, self.headers, self.body)
+    }
+}
+
+enum Exchange {
+    // The connection closed before the response started.
+    Stale,
+    Failed(IoError),
+}
+
+fn closed_early() -> ClientError {
+    ClientError::Io(IoError::new(
+        ErrorKind::UnexpectedEof,
+        "connection closed before a response",
+    ))
+}
+
+fn request_head(method: &Method, url: &Url, headers: &Headers, body_length: usize) -> Vec<u8> {
+    let mut headers = headers.clone();
+    if !headers.contains("Host") {
+        headers.insert("Host", &url.authority());
+    }
+    if body_length > 0 || matches!(method, Method::POST | Method::PUT | Method::PATCH) {
+        headers.insert("Content-Length", &body_length.to_string());
+    }
+    headers.remove("Transfer-Encoding");
+
+    let mut head = format!("{} {} HTTP/1.1\r\n", method, url.target);
+    for (name, value) in headers.iter() {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    head.into_bytes()
+}
+
+// Sends one request and reads the whole response, reporting whether the connection can be reused.
+fn exchange(
+    connection: &mut BufReader<TcpStream>,
+    method: &Method,
+    head: &[u8],
+    body: &[u8],
+) -> Result<(Response, bool), Exchange> {
+    let stream = connection.get_mut();
+    stream
+        .write_all(head)
+        .and_then(|_| stream.write_all(body))
+        .and_then(|_| stream.flush())
+        .map_err(|_| Exchange::Stale)?;
+
+    let (status_code, mut headers) = read_final_head(connection).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset => Exchange::Stale,
+        _ => Exchange::Failed(e),
+    })?;
+    let framing = Framing::of(method, status_code, &mut headers).map_err(Exchange::Failed)?;
+
+    let mut bytes = Vec::new();
+    let read = match framing {
+        Framing::Empty => Ok(0),
+        Framing::Length(length) => connection.by_ref().take(length).read_to_end(&mut bytes),
+        Framing::Chunked => ChunkedReader::new(connection.by_ref()).read_to_end(&mut bytes),
+        Framing::UntilClose => connection.read_to_end(&mut bytes),
+    };
+    read.map_err(Exchange::Failed)?;
+    if let Framing::Length(length) = framing {
+        if (bytes.len() as u64) < length {
+            return Err(Exchange::Failed(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "response body shorter than its Content-Length",
+            )));
+        }
+    }
+
+    let closes = headers
+        .get("Connection")
+        .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+    let reusable = framing != Framing::UntilClose && !closes;
+
+    let mut response = Response::new(status_code).with_body(bytes);
+    response.headers = headers;
+    Ok((response, reusable))
+}
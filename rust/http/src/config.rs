@@ -0,0 +1,579 @@
+use crate::middleware::{KeyBy, Rate, RateLimit};
+use crate::server::{LogFormat, Server};
+#[cfg(feature = "tls")]
+use crate::tls::{Certificate, TlsConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::io::Error as IoError;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const ENV_PREFIX: &str = "HTTP_";
+
+// Scalar settings that can be overridden from the environment or the command line.
+pub const SETTINGS: [&str; 20] = [
+    "listen",
+    "workers",
+    "keep_alive_timeout",
+    "write_timeout",
+    "max_body_size",
+    "max_connections",
+    "max_connections_per_ip",
+    "log_format",
+    "static_root",
+    "metrics_path",
+    "health_checks",
+    "shutdown_timeout",
+    "admin_listen",
+    "runtime",
+    "tls_cert",
+    "tls_key",
+    "https_redirect",
+    "record_har",
+    "rate_limit",
+    "rate_limit_burst",
+];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, IoError),
+    Parse(PathBuf, String),
+    InvalidValue {
+        setting: String,
+        value: String,
+        reason: String,
+    },
+    Invalid(String),
+}
+
+impl ConfigError {
+    fn invalid_value(setting: &str, value: &str, reason: &str) -> Self {
+        ConfigError::InvalidValue {
+            setting: setting.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            Self::Parse(path, message) => write!(f, "invalid {}: {}", path.display(), message),
+            Self::InvalidValue {
+                setting,
+                value,
+                reason,
+            } => write!(f, "invalid value {:?} for `{}`: {}", value, setting, reason),
+            Self::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// "async" needs the binary to be built with the `async` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Runtime {
+    #[default]
+    Threads,
+    Async,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen: Vec<String>,
+    pub workers: Option<usize>,
+    // Seconds.
+    pub keep_alive_timeout: u64,
+    pub write_timeout: Option<u64>,
+    pub max_body_size: usize,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub log_format: LogFormat,
+    // Served at "/" after every mount.
+    pub static_root: Option<PathBuf>,
+    // Serves Prometheus metrics at this path on the admin listener, e.g. "/metrics".
+    pub metrics_path: Option<String>,
+    // Adds /healthz and /readyz to the main listener.
+    pub health_checks: bool,
+    pub shutdown_timeout: u64,
+    // Serves the admin routes on their own address, e.g. "127.0.0.1:9090".
+    pub admin_listen: Option<String>,
+    pub runtime: Runtime,
+    // PEM files; when both are set every `listen` address serves HTTPS.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    // Redirects plain HTTP on this address to HTTPS, e.g. "0.0.0.0:80".
+    pub https_redirect: Option<String>,
+    // Records every exchange on the main listener to this HAR file, credentials redacted.
+    pub record_har: Option<PathBuf>,
+    // Requests per second each client address may make on the main listener; 0 turns the
+    // limit off.
+    pub rate_limit: f64,
+    // Requests a client may make at once before the per-second rate applies.
+    pub rate_limit_burst: u32,
+    #[serde(rename = "mount")]
+    pub mounts: Vec<Mount>,
+    // Extra certificates chosen by the server name clients ask for (SNI).
+    #[serde(rename = "certificate")]
+    pub certificates: Vec<HostCertificate>,
+}
+
+// A path prefix served by a static directory, a set of proxy upstreams or a CGI script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mount {
+    pub path: String,
+    #[serde(rename = "static", default, skip_serializing_if = "Option::is_none")]
+    pub static_root: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxy: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgi: Option<PathBuf>,
+    // Seconds a CGI script may run.
+    #[serde(default = "default_cgi_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostCertificate {
+    // "example.com" or "*.example.com".
+    pub host: String,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+fn default_cgi_timeout() -> u64 {
+    30
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: vec![String::from("127.0.0.1:8080")],
+            workers: None,
+            keep_alive_timeout: 5,
+            write_timeout: None,
+            max_body_size: 10 * 1024 * 1024,
+            max_connections: Some(256),
+            max_connections_per_ip: Some(16),
+            log_format: LogFormat::Text,
+            static_root: None,
+            metrics_path: None,
+            health_checks: false,
+            shutdown_timeout: 30,
+            admin_listen: None,
+            runtime: Runtime::Threads,
+            tls_cert: None,
+            tls_key: None,
+            https_redirect: None,
+            record_har: None,
+            rate_limit: 10.0,
+            rate_limit_burst: 20,
+            mounts: Vec::new(),
+            certificates: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))
+    }
+
+    // Applies `HTTP_<SETTING>` variables, e.g. HTTP_WORKERS=8 or HTTP_LISTEN=a:80,b:80.
+    pub fn apply_env(
+        &mut self,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<(), ConfigError> {
+        for (name, value) in vars {
+            if let Some(setting) = name.strip_prefix(ENV_PREFIX) {
+                let setting = setting.to_ascii_lowercase();
+                if SETTINGS.contains(&setting.as_str()) {
+                    self.set(&setting, &value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, setting: &str, value: &str) -> Result<(), ConfigError> {
+        let number = |value: &str| {
+            value
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| ConfigError::invalid_value(setting, value, "expected a number"))
+        };
+        match setting {
+            "listen" => {
+                self.listen = value
+                    .split(',')
+                    .map(|address| address.trim().to_string())
+                    .filter(|address| !address.is_empty())
+                    .collect();
+            }
+            "workers" => self.workers = Some(number(value)? as usize),
+            "keep_alive_timeout" => self.keep_alive_timeout = number(value)?,
+            "write_timeout" => self.write_timeout = Some(number(value)?),
+            "max_body_size" => self.max_body_size = number(value)? as usize,
+            "max_connections" => self.max_connections = Some(number(value)? as usize),
+            "max_connections_per_ip" => self.max_connections_per_ip = Some(number(value)? as usize),
+            "log_format" => {
+                self.log_format = match value.trim() {
+                    "text" => LogFormat::Text,
+                    "json" => LogFormat::Json,
+                    _ => {
+                        return Err(ConfigError::invalid_value(
+                            setting,
+                            value,
+                            "expected `text` or `json`",
+                        ))
+                    }
+                }
+            }
+            "static_root" => self.static_root = Some(PathBuf::from(value)),
+            "metrics_path" => self.metrics_path = Some(value.trim().to_string()),
+            "health_checks" => {
+                self.health_checks = match value.trim() {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => {
+                        return Err(ConfigError::invalid_value(
+                            setting,
+                            value,
+                            "expected `true` or `false`",
+                        ))
+                    }
+                }
+            }
+            "shutdown_timeout" => self.shutdown_timeout = number(value)?,
+            "admin_listen" => self.admin_listen = Some(value.trim().to_string()),
+            "runtime" => {
+                self.runtime = match value.trim() {
+                    "threads" => Runtime::Threads,
+                    "async" => Runtime::Async,
+                    _ => {
+                        return Err(ConfigError::invalid_value(
+                            setting,
+                            value,
+                            "expected `threads` or `async`",
+                        ))
+                    }
+                }
+            }
+            "tls_cert" => self.tls_cert = Some(PathBuf::from(value)),
+            "tls_key" => self.tls_key = Some(PathBuf::from(value)),
+            "https_redirect" => self.https_redirect = Some(value.trim().to_string()),
+            "record_har" => self.record_har = Some(PathBuf::from(value)),
+            "rate_limit" => {
+                self.rate_limit = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| ConfigError::invalid_value(setting, value, "expected a number"))?
+            }
+            "rate_limit_burst" => {
+                self.rate_limit_burst = u32::try_from(number(value)?)
+                    .map_err(|_| ConfigError::invalid_value(setting, value, "is too large"))?
+            }
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown setting `{}`",
+                    setting
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.listen.is_empty() {
+            return Err(ConfigError::Invalid(String::from(
+                "`listen` needs at least one address",
+            )));
+        }
+        for address in &self.listen {
+            check_address("listen", address)?;
+        }
+        if self.workers == Some(0) {
+            return Err(ConfigError::invalid_value(
+                "workers",
+                "0",
+                "must be at least 1",
+            ));
+        }
+        if self.keep_alive_timeout == 0 {
+            return Err(ConfigError::invalid_value(
+                "keep_alive_timeout",
+                "0",
+                "must be at least 1 second",
+            ));
+        }
+        if self.write_timeout == Some(0) {
+            return Err(ConfigError::invalid_value(
+                "write_timeout",
+                "0",
+                "must be at least 1 second",
+            ));
+        }
+        if self.max_body_size == 0 {
+            return Err(ConfigError::invalid_value(
+                "max_body_size",
+                "0",
+                "must be greater than 0",
+            ));
+        }
+        if let Some(root) = &self.static_root {
+            check_directory("static_root", root)?;
+        }
+        if let Some(path) = &self.metrics_path {
+            if !path.starts_with('/') {
+                return Err(ConfigError::invalid_value(
+                    "metrics_path",
+                    path,
+                    "must start with `/`",
+                ));
+            }
+            if self.admin_listen.is_none() {
+                return Err(ConfigError::invalid_value(
+                    "metrics_path",
+                    path,
+                    "is served on the admin listener, so `admin_listen` must be set",
+                ));
+            }
+        }
+        if !self.rate_limit.is_finite() || self.rate_limit < 0.0 {
+            return Err(ConfigError::invalid_value(
+                "rate_limit",
+                &self.rate_limit.to_string(),
+                "must be 0 or a positive number",
+            ));
+        }
+        if self.rate_limit > 0.0 && self.rate_limit_burst == 0 {
+            return Err(ConfigError::invalid_value(
+                "rate_limit_burst",
+                "0",
+                "must be at least 1 while `rate_limit` is on",
+            ));
+        }
+        if self.runtime == Runtime::Async && !cfg!(feature = "async") {
+            return Err(ConfigError::invalid_value(
+                "runtime",
+                "async",
+                "this binary was built without the `async` feature",
+            ));
+        }
+        if let Some(address) = &self.admin_listen {
+            check_address("admin_listen", address)?;
+            if self.listen.contains(address) {
+                return Err(ConfigError::invalid_value(
+                    "admin_listen",
+                    address,
+                    "must differ from every `listen` address",
+                ));
+            }
+        }
+        self.validate_tls()?;
+
+        let mut paths = HashSet::new();
+        for (i, mount) in self.mounts.iter().enumerate() {
+            let setting = format!("mount[{}].path", i);
+            if !mount.path.starts_with('/') || mount.path.len() > 1 && mount.path.ends_with('/') {
+                return Err(ConfigError::invalid_value(
+                    &setting,
+                    &mount.path,
+                    "must start with `/` and not end with one",
+                ));
+            }
+            if !paths.insert(mount.path.as_str()) {
+                return Err(ConfigError::invalid_value(
+                    &setting,
+                    &mount.path,
+                    "mounted more than once",
+                ));
+            }
+            match (&mount.static_root, mount.proxy.is_empty(), &mount.cgi) {
+                (Some(root), true, None) => check_directory(&format!("mount[{}].static", i), root)?,
+                (None, false, None) => {
+                    for upstream in &mount.proxy {
+                        check_address(&format!("mount[{}].proxy", i), upstream)?;
+                    }
+                }
+                (None, true, Some(script)) => {
+                    if !script.is_file() {
+                        return Err(ConfigError::invalid_value(
+                            &format!("mount[{}].cgi", i),
+                            &script.display().to_string(),
+                            "is not a file",
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "mount[{}] ({}) needs exactly one of `static`, `proxy` or `cgi`",
+                        i, mount.path
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_tls(&self) -> Result<(), ConfigError> {
+        let enabled = match (&self.tls_cert, &self.tls_key) {
+            (Some(_), Some(_)) => true,
+            (None, None) => false,
+            _ => {
+                return Err(ConfigError::Invalid(String::from(
+                    "`tls_cert` and `tls_key` must be set together",
+                )))
+            }
+        };
+        if !enabled {
+            if self.https_redirect.is_some() || !self.certificates.is_empty() {
+                return Err(ConfigError::Invalid(String::from(
+                    "`https_redirect` and `certificate` need `tls_cert` and `tls_key`",
+                )));
+            }
+            return Ok(());
+        }
+        if !cfg!(feature = "tls") {
+            return Err(ConfigError::Invalid(String::from(
+                "`tls_cert` needs a binary built with the `tls` feature",
+            )));
+        }
+        if self.runtime == Runtime::Async {
+            return Err(ConfigError::invalid_value(
+                "runtime",
+                "async",
+                "TLS is only supported by the `threads` runtime",
+            ));
+        }
+        if let Some(address) = &self.https_redirect {
+            check_address("https_redirect", address)?;
+            if self.listen.contains(address) {
+                return Err(ConfigError::invalid_value(
+                    "https_redirect",
+                    address,
+                    "must differ from every `listen` address",
+                ));
+            }
+        }
+        #[cfg(feature = "tls")]
+        self.tls()?;
+        Ok(())
+    }
+
+    // Loads the certificates, or None when TLS is not configured.
+    #[cfg(feature = "tls")]
+    pub fn tls(&self) -> Result<Option<TlsConfig>, ConfigError> {
+        let load = |setting: &str, cert: &Path, key: &Path| {
+            Certificate::from_pem_files(cert, key).map_err(|e| {
+                ConfigError::invalid_value(setting, &cert.display().to_string(), &e.to_string())
+            })
+        };
+        let (cert, key) = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => (cert, key),
+            _ => return Ok(None),
+        };
+        let mut tls = TlsConfig::new(load("tls_cert", cert, key)?);
+        for (i, certificate) in self.certificates.iter().enumerate() {
+            let setting = format!("certificate[{}].cert", i);
+            tls = tls.host(
+                &certificate.host,
+                load(&setting, &certificate.cert, &certificate.key)?,
+            );
+        }
+        Ok(Some(tls))
+    }
+
+    // The per-client limit for the main listener, or None when `rate_limit` is 0.
+    pub fn rate_limiter(&self) -> Option<RateLimit> {
+        if self.rate_limit == 0.0 {
+            return None;
+        }
+        Some(
+            RateLimit::new(KeyBy::RemoteAddr)
+                .default_rate(Rate::new(self.rate_limit, self.rate_limit_burst)),
+        )
+    }
+
+    pub fn server(&self) -> Server {
+        let (ip_address, port) = split_address(&self.listen[0]);
+        let mut server = Server::new(ip_address, port)
+            .keep_alive_timeout(Duration::from_secs(self.keep_alive_timeout))
+            .max_body_size(self.max_body_size)
+            .log_format(self.log_format)
+            .shutdown_timeout(Duration::from_secs(self.shutdown_timeout));
+        for address in &self.listen[1..] {
+            server = server.listen(address);
+        }
+        if let Some(workers) = self.workers {
+            server = server.workers(workers);
+        }
+        if let Some(timeout) = self.write_timeout {
+            server = server.write_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(limit) = self.max_connections {
+            server = server.max_connections(limit);
+        }
+        if let Some(limit) = self.max_connections_per_ip {
+            server = server.max_connections_per_ip(limit);
+        }
+        #[cfg(feature = "tls")]
+        if let Some(address) = &self.https_redirect {
+            server = server.redirect_http(address);
+        }
+        server
+    }
+
+    pub fn admin_server(&self) -> Option<Server> {
+        let (ip_address, port) = split_address(self.admin_listen.as_deref()?);
+        Some(Server::new(ip_address, port).log_format(self.log_format))
+    }
+}
+
+fn split_address(address: &str) -> (String, u32) {
+    match address.rsplit_once(':') {
+        Some((ip_address, port)) => (ip_address.to_string(), port.parse().unwrap_or(0)),
+        None => (address.to_string(), 0),
+    }
+}
+
+fn check_address(setting: &str, address: &str) -> Result<(), ConfigError> {
+    match address.to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(ConfigError::invalid_value(
+            setting,
+            address,
+            "does not resolve",
+        )),
+        Err(e) => Err(ConfigError::invalid_value(
+            setting,
+            address,
+            &format!("expected host:port ({})", e),
+        )),
+    }
+}
+
+fn check_directory(setting: &str, path: &Path) -> Result<(), ConfigError> {
+    if path.is_dir() {
+        Ok(())
+    } else {
+        Err(ConfigError::invalid_value(
+            setting,
+            &path.display().to_string(),
+            "is not a directory",
+        ))
+    }
+}
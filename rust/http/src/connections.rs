@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct Counts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+// Counts open connections so the accept loop can turn away clients over the caps.
+#[derive(Clone, Default)]
+pub struct ConnectionTracker {
+    max_total: Option<usize>,
+    max_per_ip: Option<usize>,
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl ConnectionTracker {
+    pub fn new(max_total: Option<usize>, max_per_ip: Option<usize>) -> Self {
+        ConnectionTracker {
+            max_total,
+            max_per_ip,
+            counts: Arc::new(Mutex::new(Counts::default())),
+        }
+    }
+
+    // Shares this tracker's counts but enforces different caps.
+    pub fn with_limits(&self, max_total: Option<usize>, max_per_ip: Option<usize>) -> Self {
+        ConnectionTracker {
+            max_total,
+            max_per_ip,
+            counts: Arc::clone(&self.counts),
+        }
+    }
+
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<ConnectionGuard> {
+        let mut counts = self.counts.lock().unwrap();
+        if self.max_total.is_some_and(|max| counts.total >= max) {
+            return None;
+        }
+        let for_ip = counts.per_ip.get(&ip).copied().unwrap_or(0);
+        if self.max_per_ip.is_some_and(|max| for_ip >= max) {
+            return None;
+        }
+
+        counts.total += 1;
+        counts.per_ip.insert(ip, for_ip + 1);
+        Some(ConnectionGuard {
+            ip,
+            counts: Arc::clone(&self.counts),
+        })
+    }
+
+    pub fn active(&self) -> usize {
+        self.counts.lock().unwrap().total
+    }
+}
+
+pub struct ConnectionGuard {
+    ip: IpAddr,
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut counts = self.counts.lock().unwrap();
+        counts.total -= 1;
+        if let Some(for_ip) = counts.per_ip.get_mut(&self.ip) {
+            *for_ip -= 1;
+            if *for_ip == 0 {
+                counts.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::server::Handler;
+use std::io::{Read, Write};
+use std::path::{self, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Runs a CGI/1.1 script (RFC 3875) per request and turns its output into a response.
+pub struct Cgi {
+    script: PathBuf,
+    strip_prefix: Option<String>,
+    timeout: Duration,
+    max_output: usize,
+    env: Vec<(String, String)>,
+}
+
+impl Cgi {
+    // A relative `script` is resolved against the current directory now, as each run
+    // starts in the script's own directory.
+    pub fn new(script: impl Into<PathBuf>) -> Self {
+        let script = script.into();
+        Cgi {
+            script: path::absolute(&script).unwrap_or(script),
+            strip_prefix: None,
+            timeout: Duration::from_secs(30),
+            max_output: 16 * 1024 * 1024,
+            env: Vec::new(),
+        }
+    }
+
+    // The mount prefix becomes SCRIPT_NAME and the rest of the path PATH_INFO.
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.strip_prefix = Some(prefix.to_string());
+        self
+    }
+
+    // Scripts still running after `timeout` are killed and answered with 504.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Scripts that print more than `bytes` are answered with 502 instead of buffering it all.
+    pub fn max_output(mut self, bytes: usize) -> Self {
+        self.max_output = bytes;
+        self
+    }
+
+    // Extra variables passed to every run, e.g. PATH or a config location.
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn command(&self, request: &Request) -> Command {
+        let (script_name, path_info) = match &self.strip_prefix {
+            Some(prefix) => match request.path.strip_prefix(prefix.as_str()) {
+                Some(rest) => (prefix.as_str(), rest),
+                None => ("", request.path.as_str()),
+            },
+            None => ("", request.path.as_str()),
+        };
+        let host = request.headers.get("Host").unwrap_or("");
+        let (server_name, server_port) = match host.rsplit_once(':') {
+            Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => (name, port),
+            _ => (host, "80"),
+        };
+
+        let mut command = Command::new(&self.script);
+        command
+            .env_clear()
+            .env("GATEWAY_INTERFACE", "CGI/1.1")
+            .env("SERVER_PROTOCOL", "HTTP/1.1")
+            .env("SERVER_SOFTWARE", "http")
+            .env("SERVER_NAME", server_name)
+            .env("SERVER_PORT", server_port)
+            .env("REQUEST_METHOD", request.method.to_string())
+            .env(
+                "QUERY_STRING",
+                request.query_string.as_deref().unwrap_or(""),
+            )
+            .env("SCRIPT_NAME", script_name)
+            .env("PATH_INFO", path_info)
+            .env(
+                "REQUEST_URI",
+                match &request.query_string {
+                    Some(query) => format!("{}?{}", request.path, query),
+                    None => request.path.clone(),
+                },
+            );
+        if let Some(addr) = request.remote_addr {
+            command
+                .env("REMOTE_ADDR", addr.ip().to_string())
+                .env("REMOTE_PORT", addr.port().to_string());
+        }
+        if !request.body.is_empty() {
+            command.env("CONTENT_LENGTH", request.body.len().to_string());
+        }
+        if let Some(content_type) = request.headers.get("Content-Type") {
+            command.env("CONTENT_TYPE", content_type);
+        }
+        for (name, value) in request.headers.iter() {
+            // A client's Proxy header would become HTTP_PROXY, which many HTTP libraries
+            // read as their outgoing proxy (httpoxy, CVE-2016-5385).
+            if name.eq_ignore_ascii_case("Content-Type")
+                || name.eq_ignore_ascii_case("Content-Length")
+                || name.eq_ignore_ascii_case("Authorization")
+                || name.eq_ignore_ascii_case("Proxy")
+            {
+                continue;
+            }
+            command.env(
+                format!("HTTP_{}", name.to_ascii_uppercase().replace('-', "_")),
+                value,
+            );
+        }
+        for (name, value) in &self.env {
+            command.env(name, value);
+        }
+        if let Some(dir) = self
+            .script
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+impl Handler for Cgi {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        let mut child = match self
+            .command(request)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                println!("Failed to run CGI script {}: {}", self.script.display(), e);
+                return Response::new(StatusCode::INTERNAL_SERVER_ERROR)
+                    .with_body("Internal Server Error");
+            }
+        };
+
+        // Feed stdin and drain the pipes on their own threads so a chatty script cannot
+        // deadlock against us.
+        let body = std::mem::take(&mut request.body);
+        let stdin = child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || {
+                let _ = stdin.write_all(&body);
+            })
+        });
+        let stdout = child
+            .stdout
+            .take()
+            .map(|pipe| read_all(pipe, self.max_output));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|pipe| read_all(pipe, self.max_output));
+
+        if !wait_for(&mut child, self.timeout) {
+            println!(
+                "CGI script {} timed out after {:?}",
+                self.script.display(),
+                self.timeout
+            );
+            let _ = child.kill();
+            let _ = child.wait();
+            return Response::new(StatusCode::GATEWAY_TIMEOUT).with_body("Gateway Timeout");
+        }
+
+        if let Some(stdin) = stdin {
+            let _ = stdin.join();
+        }
+        let output = stdout
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        let errors = stderr
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        if !errors.is_empty() {
+            println!(
+                "CGI script {}: {}",
+                self.script.display(),
+                String::from_utf8_lossy(&errors).trim_end()
+            );
+        }
+
+        if output.len() > self.max_output {
+            println!(
+                "CGI script {} printed more than {} bytes",
+                self.script.display(),
+                self.max_output
+            );
+            return Response::new(StatusCode::BAD_GATEWAY).with_body("Bad Gateway");
+        }
+        parse_output(&output).unwrap_or_else(|| {
+            println!(
+                "CGI script {} produced no valid headers",
+                self.script.display()
+            );
+            Response::new(StatusCode::BAD_GATEWAY).with_body("Bad Gateway")
+        })
+    }
+}
+
+// Reads at most one byte more than `limit`, so going over it can be told apart. The pipe is
+// then closed, which stops a script that keeps writing.
+fn read_all(pipe: impl Read + Send + 'static, limit: usize) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe
+            .take(limit.saturating_add(1) as u64)
+            .read_to_end(&mut output);
+        output
+    })
+}
+
+// True once the child has exited, false if it is still running at the deadline.
+fn wait_for(child: &mut Child, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => return true,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Ok(None) => return false,
+            Err(_) => return true,
+        }
+    }
+}
+
+// Script output is a header block, a blank line, then the body. Lines may end in "\n" or
+// "\r\n". "Status" sets the status line and a bare "Location" means a redirect. Output with
+// a status outside 200 to 599 is invalid, as an interim 1xx cannot end the exchange.
+fn parse_output(output: &[u8]) -> Option<Response> {
+    let mut rest = output;
+    let mut headers = Vec::new();
+    loop {
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        let line = std::str::from_utf8(&rest[..end]).ok()?;
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        rest = &rest[end + 1..];
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    if headers.is_empty() {
+        return None;
+    }
+
+    let mut status = None;
+    let mut response_headers = Vec::new();
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case("Status") {
+            let code = value.split_whitespace().next()?.parse().ok()?;
+            if !(200..=599).contains(&code) {
+                return None;
+            }
+            status = Some(StatusCode(code));
+        } else if name.eq_ignore_ascii_case("Content-Length")
+            || name.eq_ignore_ascii_case("Transfer-Encoding")
+            || name.eq_ignore_ascii_case("Connection")
+        {
+            // The body is already buffered, so the server frames it itself.
+            continue;
+        } else {
+            response_headers.push((name, value));
+        }
+    }
+    let status = status.unwrap_or_else(|| {
+        if response_headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Location"))
+        {
+            StatusCode::FOUND
+        } else {
+            StatusCode::OK
+        }
+    });
+
+    let mut response = Response::new(status);
+    for (name, value) in response_headers {
+        response.headers.append(&name, &value);
+    }
+    Some(response.with_body(rest.to_vec()))
+}
@@ -0,0 +1,7 @@
+pub use cgi::Cgi;
+pub use proxy::Proxy;
+pub use static_files::StaticFiles;
+
+pub mod cgi;
+pub mod proxy;
+pub mod static_files;
@@ -0,0 +1,248 @@
+use crate::http::{Headers, Method, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::io::{BufReader, Result as IoResult, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const HOP_BY_HOP: [&str; 8] = [
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+#[derive(Default)]
+struct Health {
+    consecutive_failures: u32,
+    ejected_until: Option<Instant>,
+}
+
+struct Upstream {
+    address: String,
+    health: Mutex<Health>,
+}
+
+impl Upstream {
+    fn is_available(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap();
+        health.ejected_until.is_none_or(|until| until <= now)
+    }
+
+    fn record_success(&self) {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures = 0;
+        health.ejected_until = None;
+    }
+
+    fn record_failure(&self, max_failures: u32, eject_for: Duration) {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= max_failures {
+            println!("Ejecting upstream {} for {:?}", self.address, eject_for);
+            health.ejected_until = Some(Instant::now() + eject_for);
+        }
+    }
+}
+
+// Forwards requests round-robin to plain HTTP/1.1 upstreams, ejecting ones that keep failing.
+pub struct Proxy {
+    upstreams: Vec<Upstream>,
+    next: AtomicUsize,
+    strip_prefix: Option<String>,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    max_failures: u32,
+    eject_for: Duration,
+}
+
+impl Proxy {
+    pub fn new(upstreams: &[&str]) -> Self {
+        Proxy {
+            upstreams: upstreams
+                .iter()
+                .map(|address| Upstream {
+                    address: address.to_string(),
+                    health: Mutex::new(Health::default()),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+            strip_prefix: None,
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(30),
+            max_failures: 3,
+            eject_for: Duration::from_secs(30),
+        }
+    }
+
+    // Removes a mount prefix such as "/api" before the path is sent upstream.
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.strip_prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn timeouts(mut self, connect: Duration, read: Duration) -> Self {
+        self.connect_timeout = connect;
+        self.read_timeout = read;
+        self
+    }
+
+    pub fn ejection(mut self, max_failures: u32, eject_for: Duration) -> Self {
+        self.max_failures = max_failures.max(1);
+        self.eject_for = eject_for;
+        self
+    }
+
+    // Healthy upstreams in round-robin order; when all are ejected, try them anyway.
+    fn candidates(&self) -> Vec<&Upstream> {
+        let count = self.upstreams.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let ordered: Vec<&Upstream> = (0..count)
+            .map(|i| &self.upstreams[(start + i) % count])
+            .collect();
+        let healthy: Vec<&Upstream> = ordered
+            .iter()
+            .copied()
+            .filter(|upstream| upstream.is_available(now))
+            .collect();
+        if healthy.is_empty() {
+            ordered
+        } else {
+            healthy
+        }
+    }
+
+    fn connect(&self, upstream: &Upstream) -> IoResult<TcpStream> {
+        let mut last_error = None;
+        for addr in upstream.address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.read_timeout))?;
+                    stream.set_write_timeout(Some(self.read_timeout))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "upstream did not resolve")
+        }))
+    }
+
+    fn upstream_path(&self, request: &Request) -> String {
+        let mut path = request.path.as_str();
+        if let Some(prefix) = &self.strip_prefix {
+            path = path.strip_prefix(prefix.as_str()).unwrap_or(path);
+        }
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+        match &request.query_string {
+            Some(query_string) => format!("{}?{}", path, query_string),
+            None => path,
+        }
+    }
+
+    fn upstream_head(&self, request: &Request, upstream: &Upstream) -> Vec<u8> {
+        let mut headers = forwarded_headers(&request.headers);
+        // The client was already answered with 100 Continue and the body is in hand.
+        headers.remove("Expect");
+        if let Some(host) = request.headers.get("Host") {
+            headers.insert("X-Forwarded-Host", host);
+        }
+        headers.insert("Host", &upstream.address);
+        if let Some(addr) = request.remote_addr {
+            let forwarded_for = match request.headers.get("X-Forwarded-For") {
+                Some(previous) => format!("{}, {}", previous, addr.ip()),
+                None => addr.ip().to_string(),
+            };
+            headers.insert("X-Forwarded-For", &forwarded_for);
+        }
+        headers.insert("X-Forwarded-Proto", "http");
+        headers.insert("Connection", "close");
+        headers.insert("Content-Length", &request.body.len().to_string());
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\n",
+            request.method,
+            self.upstream_path(request)
+        );
+        for (name, value) in headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        head.into_bytes()
+    }
+
+    fn forward(
+        &self,
+        request: &Request,
+        upstream: &Upstream,
+        mut stream: TcpStream,
+    ) -> IoResult<Response> {
+        stream.write_all(&self.upstream_head(request, upstream))?;
+        stream.write_all(&request.body)?;
+        stream.flush()?;
+
+        let mut response = Response::read_from(BufReader::new(stream), &request.method)?;
+        let mut headers = forwarded_headers(&response.headers);
+        if request.method == Method::HEAD {
+            if let Some(length) = response.headers.get("Content-Length") {
+                headers.insert("Content-Length", length);
+            }
+        }
+        response.headers = headers;
+        Ok(response)
+    }
+}
+
+impl Handler for Proxy {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        // Only connection failures move on to the next upstream, as nothing has been sent yet.
+        for upstream in self.candidates() {
+            let stream = match self.connect(upstream) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("Failed to connect to upstream {}: {}", upstream.address, e);
+                    upstream.record_failure(self.max_failures, self.eject_for);
+                    continue;
+                }
+            };
+            return match self.forward(request, upstream, stream) {
+                Ok(response) => {
+                    upstream.record_success();
+                    response
+                }
+                Err(e) => {
+                    println!("Upstream {} failed: {}", upstream.address, e);
+                    upstream.record_failure(self.max_failures, self.eject_for);
+                    Response::new(StatusCode::BAD_GATEWAY).with_body("Bad Gateway")
+                }
+            };
+        }
+        Response::new(StatusCode::BAD_GATEWAY).with_body("Bad Gateway")
+    }
+}
+
+fn forwarded_headers(headers: &Headers) -> Headers {
+    let mut forwarded = headers.clone();
+    // Headers named in Connection are hop-by-hop too.
+    if let Some(connection) = headers.get("Connection") {
+        for name in connection.split(',') {
+            forwarded.remove(name.trim());
+        }
+    }
+    for name in HOP_BY_HOP {
+        forwarded.remove(name);
+    }
+    forwarded.remove("Content-Length");
+    forwarded
+}
@@ -0,0 +1,98 @@
+use crate::http::path::percent_decode;
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+// Serves files below `root` for GET and HEAD, with `index.html` for directories.
+pub struct StaticFiles {
+    root: PathBuf,
+    strip_prefix: Option<String>,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        StaticFiles {
+            root: root.into(),
+            strip_prefix: None,
+        }
+    }
+
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.strip_prefix = Some(prefix.to_string());
+        self
+    }
+
+    // Rejects any path that could leave `root`, such as one containing "..".
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = match &self.strip_prefix {
+            Some(prefix) => path.strip_prefix(prefix.as_str()).unwrap_or(path),
+            None => path,
+        };
+        let decoded = percent_decode(path)?;
+        let relative = Path::new(decoded.trim_start_matches('/'));
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+
+        let mut full = self.root.join(relative);
+        if full.is_dir() {
+            full.push("index.html");
+        }
+        Some(full)
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        if request.method != Method::GET && request.method != Method::HEAD {
+            return Response::new(StatusCode::METHOD_NOT_ALLOWED)
+                .with_header("Allow", "GET, HEAD")
+                .with_body("Method Not Allowed");
+        }
+        let not_found = || Response::new(StatusCode::NOT_FOUND).with_body("Not Found");
+
+        let path = match self.resolve(&request.path) {
+            Some(path) => path,
+            None => return not_found(),
+        };
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => return not_found(),
+        };
+        let length = match file.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => return not_found(),
+        };
+
+        Response::new(StatusCode::OK)
+            .with_header("Content-Type", content_type(&path))
+            .with_reader(file, Some(length))
+    }
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,346 @@
+use crate::http::{Headers, Request, Response};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// The subset of HAR 1.2 (http://www.softwareishard.com/blog/har-12-spec/) needed to
+// describe one HTTP/1.1 exchange. Unknown fields are ignored when loading, so archives
+// from browsers can be replayed too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub started_date_time: String,
+    // Milliseconds.
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    pub timings: Timings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<NameValue>,
+    pub headers: Vec<NameValue>,
+    #[serde(default)]
+    pub query_string: Vec<NameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<NameValue>,
+    pub headers: Vec<NameValue>,
+    pub content: Content,
+    #[serde(rename = "redirectURL", default)]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameValue {
+    pub name: String,
+    pub value: String,
+}
+
+// Request bodies have no `encoding` in HAR 1.2, hence the custom `_encoding` field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    pub mime_type: String,
+    pub text: String,
+    #[serde(rename = "_encoding", default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+// How a body was captured: the recorded text, whether it is base64, and why it is
+// incomplete, if it is.
+pub(crate) struct CapturedBody {
+    pub text: Option<String>,
+    pub encoding: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl Har {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Har {
+            log: Log {
+                version: String::from("1.2"),
+                creator: Creator {
+                    name: String::from(env!("CARGO_PKG_NAME")),
+                    version: String::from(env!("CARGO_PKG_VERSION")),
+                },
+                entries,
+            },
+        }
+    }
+
+    pub fn load(path: &Path) -> IoResult<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+    }
+
+    // Writes a temporary file next to `path` and renames it over, so readers never see
+    // a half-written archive.
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, json)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+impl Entry {
+    // `redact` decides which header values are replaced; bodies past `max_body_size` are cut.
+    pub(crate) fn new(
+        request: &Request,
+        response: &Response,
+        started: SystemTime,
+        elapsed: Duration,
+        max_body_size: usize,
+        redact: &dyn Fn(&str) -> bool,
+    ) -> Self {
+        let host = request.headers.get("Host").unwrap_or("localhost");
+        let mut url = format!("http://{}{}", host, request.path);
+        let mut query_string = Vec::new();
+        if let Some(query) = &request.query_string {
+            url.push('?');
+            url.push_str(query);
+            query_string = query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    NameValue {
+                        name: name.to_string(),
+                        value: value.to_string(),
+                    }
+                })
+                .collect();
+        }
+
+        let post_data = (!request.body.is_empty()).then(|| {
+            let body = capture_body(&request.body, max_body_size);
+            PostData {
+                mime_type: mime_type(&request.headers),
+                text: body.text.unwrap_or_default(),
+                encoding: body.encoding,
+                comment: body.comment,
+            }
+        });
+        let har_request = HarRequest {
+            method: request.method.to_string(),
+            url,
+            http_version: String::from("HTTP/1.1"),
+            cookies: Vec::new(),
+            headers: name_values(&request.headers, redact),
+            query_string,
+            post_data,
+            headers_size: -1,
+            body_size: request.body.len() as i64,
+        };
+
+        let (size, body) = match response.body() {
+            Some(bytes) => (bytes.len() as i64, capture_body(bytes, max_body_size)),
+            None => (
+                -1,
+                CapturedBody {
+                    text: None,
+                    encoding: None,
+                    comment: Some(String::from("streamed body not recorded")),
+                },
+            ),
+        };
+        let status = response.status_code();
+        let har_response = HarResponse {
+            status: status.as_u16(),
+            status_text: status.reason_phrase().to_string(),
+            http_version: String::from("HTTP/1.1"),
+            cookies: Vec::new(),
+            headers: name_values(&response.headers, redact),
+            content: Content {
+                size,
+                mime_type: mime_type(&response.headers),
+                text: body.text,
+                encoding: body.encoding,
+                comment: body.comment,
+            },
+            redirect_url: response
+                .headers
+                .get("Location")
+                .unwrap_or_default()
+                .to_string(),
+            headers_size: -1,
+            body_size: size,
+        };
+
+        let wait = elapsed.as_secs_f64() * 1000.0;
+        Entry {
+            started_date_time: format_timestamp(started),
+            time: wait,
+            request: har_request,
+            response: har_response,
+            cache: serde_json::json!({}),
+            timings: Timings {
+                send: 0.0,
+                wait,
+                receive: 0.0,
+            },
+        }
+    }
+}
+
+impl HarRequest {
+    // The decoded body, or None when it was cut short or is missing from the archive.
+    pub fn body(&self) -> Option<Vec<u8>> {
+        match &self.post_data {
+            None => Some(Vec::new()),
+            Some(data) if data.comment.is_some() => None,
+            Some(data) => decode(&data.text, data.encoding.as_deref()),
+        }
+    }
+}
+
+impl Content {
+    // The decoded body, or None when it was cut short or not recorded.
+    pub fn body(&self) -> Option<Vec<u8>> {
+        match (&self.text, &self.comment) {
+            (Some(text), None) => decode(text, self.encoding.as_deref()),
+            (None, None) if self.size == 0 => Some(Vec::new()),
+            _ => None,
+        }
+    }
+}
+
+pub const REDACTED: &str = "[REDACTED]";
+
+fn name_values(headers: &Headers, redact: &dyn Fn(&str) -> bool) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: if redact(name) { REDACTED } else { value }.to_string(),
+        })
+        .collect()
+}
+
+fn mime_type(headers: &Headers) -> String {
+    headers.get("Content-Type").unwrap_or_default().to_string()
+}
+
+// Text stays readable; anything else is stored as base64.
+pub(crate) fn capture_body(bytes: &[u8], max_body_size: usize) -> CapturedBody {
+    let kept = &bytes[..bytes.len().min(max_body_size)];
+    let comment = (kept.len() < bytes.len())
+        .then(|| format!("truncated to {} of {} bytes", kept.len(), bytes.len()));
+    match std::str::from_utf8(kept) {
+        Ok(text) => CapturedBody {
+            text: Some(text.to_string()),
+            encoding: None,
+            comment,
+        },
+        Err(_) => CapturedBody {
+            text: Some(STANDARD.encode(kept)),
+            encoding: Some(String::from("base64")),
+            comment,
+        },
+    }
+}
+
+fn decode(text: &str, encoding: Option<&str>) -> Option<Vec<u8>> {
+    match encoding {
+        None => Some(text.as_bytes().to_vec()),
+        Some("base64") => STANDARD.decode(text).ok(),
+        Some(_) => None,
+    }
+}
+
+// RFC 3339 in UTC with milliseconds, e.g. "2024-05-01T12:30:00.250Z".
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
@@ -0,0 +1,168 @@
+use super::request::{self, ParseError};
+use super::{Extensions, Headers, Method};
+use std::convert::TryFrom;
+use std::str;
+
+// A request whose text and body point into the read buffer instead of being copied.
+// `into_owned` turns it into a regular `Request` for handlers that need to keep it.
+#[derive(Debug, Clone)]
+pub struct Request<'buf> {
+    pub method: Method,
+    pub path: &'buf str,
+    pub query_string: Option<&'buf str>,
+    pub headers: Vec<(&'buf str, &'buf str)>,
+    pub body: &'buf [u8],
+}
+
+impl<'buf> Request<'buf> {
+    pub fn parse(buffer: &'buf [u8]) -> Result<Self, ParseError> {
+        let (head, body) = split_head(buffer).ok_or(ParseError::InvalidRequest)?;
+        let mut request = Self::parse_lines(head)?;
+        request.body = body;
+        if let Some(length) = request.content_length()? {
+            if body.len() < length {
+                return Err(ParseError::InvalidRequest);
+            }
+            request.body = &body[..length];
+        }
+        Ok(request)
+    }
+
+    // Parses the head alone, with an empty body, e.g. to answer `Expect: 100-continue`
+    // before the body is read.
+    pub fn parse_head(buffer: &'buf [u8]) -> Result<Self, ParseError> {
+        let (head, _) = split_head(buffer).ok_or(ParseError::InvalidRequest)?;
+        let request = Self::parse_lines(head)?;
+        request.content_length()?;
+        Ok(request)
+    }
+
+    fn parse_lines(head: &'buf [u8]) -> Result<Self, ParseError> {
+        let head = str::from_utf8(head)?;
+        let mut lines = head
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line));
+        let request_line = lines.next().unwrap_or("");
+
+        let (method, request) = get_next_word(request_line).ok_or(ParseError::InvalidRequest)?;
+        let (mut path, protocol) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
+
+        if protocol != "HTTP/1.1" {
+            return Err(ParseError::InvalidProtocol);
+        }
+
+        let method: Method = method.parse()?;
+        let mut query_string = None;
+        if let Some(i) = path.find('?') {
+            query_string = Some(&path[i + 1..]);
+            path = &path[..i];
+        }
+
+        let mut headers = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':').ok_or(ParseError::InvalidHeader)?;
+            let value = value.trim_matches([' ', '\t']);
+            if name.is_empty() || !name.bytes().all(is_token_byte) {
+                return Err(ParseError::InvalidHeader);
+            }
+            if value.chars().any(|c| c.is_ascii_control() && c != '\t') {
+                return Err(ParseError::InvalidHeader);
+            }
+            headers.push((name, value));
+        }
+
+        Ok(Request {
+            method,
+            path,
+            query_string,
+            headers,
+            body: &[],
+        })
+    }
+
+    // Bodies are framed by Content-Length alone. Transfer codings are not decoded, and
+    // requests that could be framed two ways are refused instead of guessing, since a
+    // guess that differs from another server's is a request smuggling vector.
+    pub(crate) fn content_length(&self) -> Result<Option<usize>, ParseError> {
+        let length = self.header("Content-Length");
+        if self.header("Transfer-Encoding").is_some() {
+            return Err(match length {
+                Some(_) => ParseError::InvalidHeader,
+                None => ParseError::UnsupportedTransferEncoding,
+            });
+        }
+        if self.header_all("Content-Length").count() > 1 {
+            return Err(ParseError::InvalidHeader);
+        }
+        match length {
+            Some(length) if length.bytes().all(|b| b.is_ascii_digit()) => length
+                .parse()
+                .map(Some)
+                .map_err(|_| ParseError::InvalidHeader),
+            Some(_) => Err(ParseError::InvalidHeader),
+            None => Ok(None),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&'buf str> {
+        self.header_all(name).next()
+    }
+
+    pub fn header_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'buf str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    pub fn into_owned(self) -> request::Request {
+        let mut headers = Headers::new();
+        for (name, value) in &self.headers {
+            headers.append(name, value);
+        }
+        request::Request {
+            path: self.path.to_string(),
+            query_string: self.query_string.map(str::to_string),
+            method: self.method,
+            headers,
+            body: self.body.to_vec(),
+            remote_addr: None,
+            extensions: Extensions::new(),
+        }
+    }
+}
+
+impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
+    type Error = ParseError;
+
+    fn try_from(buffer: &'buf [u8]) -> Result<Self, Self::Error> {
+        Self::parse(buffer)
+    }
+}
+
+fn get_next_word(request: &str) -> Option<(&str, &str)> {
+    request
+        .find([' ', '\r'])
+        .map(|i| (&request[..i], &request[i + 1..]))
+}
+
+// The head ends at the first empty line. Lines normally end in "\r\n", but a bare "\n"
+// is accepted too (RFC 9112, section 2.2).
+fn split_head(buffer: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut line_start = 0;
+    for (i, &byte) in buffer.iter().enumerate() {
+        if byte == b'\n' {
+            let line = &buffer[line_start..i];
+            if line.is_empty() || line == b"\r" {
+                return Some((&buffer[..line_start], &buffer[i + 1..]));
+            }
+            line_start = i + 1;
+        }
+    }
+    None
+}
+
+// tchar from RFC 9110, section 5.6.2.
+pub(crate) fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
@@ -0,0 +1,95 @@
+use std::io::{BufRead, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+
+// Decodes a `Transfer-Encoding: chunked` body, stopping after the terminating chunk.
+pub struct ChunkedReader<R> {
+    inner: R,
+    remaining: u64,
+    done: bool,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub fn new(inner: R) -> Self {
+        ChunkedReader {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_line(&mut self) -> IoResult<String> {
+        let mut line = String::new();
+        if self.inner.read_line(&mut line)? == 0 {
+            return Err(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "truncated chunked body",
+            ));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn next_chunk(&mut self) -> IoResult<()> {
+        let line = self.read_line()?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        self.remaining = u64::from_str_radix(size, 16)
+            .map_err(|_| IoError::new(ErrorKind::InvalidData, "invalid chunk size"))?;
+        if self.remaining == 0 {
+            // Skip trailers up to the blank line that ends the body.
+            while !self.read_line()?.is_empty() {}
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            self.next_chunk()?;
+            if self.done {
+                return Ok(0);
+            }
+        }
+
+        let limit = buf.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buf[..limit])?;
+        if read == 0 {
+            return Err(IoError::new(ErrorKind::UnexpectedEof, "truncated chunk"));
+        }
+        self.remaining -= read as u64;
+        if self.remaining == 0 && !self.read_line()?.is_empty() {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "missing chunk terminator",
+            ));
+        }
+        Ok(read)
+    }
+}
+
+// Copies `reader` to `writer` as chunked data, including the terminating chunk.
+pub fn copy_chunked(reader: &mut dyn Read, writer: &mut dyn Write) -> IoResult<u64> {
+    let mut buffer = [0; 8 * 1024];
+    let mut total = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        write!(writer, "{:x}\r\n", read)?;
+        writer.write_all(&buffer[..read])?;
+        writer.write_all(b"\r\n")?;
+        writer.flush()?;
+        total += read as u64;
+    }
+    writer.write_all(b"0\r\n\r\n")?;
+    Ok(total)
+}
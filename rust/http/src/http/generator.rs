@@ -0,0 +1,130 @@
+use super::{Extensions, Headers, Method, Request};
+
+const PATHS: [&str; 8] = [
+    "/",
+    "/index.html",
+    "/api/users",
+    "/api/users/42/orders",
+    "/static/css/site.css",
+    "/search",
+    "/images/logo.png",
+    "/v1/items/7f3a2c/comments",
+];
+
+const HEADERS: [(&str, &str); 10] = [
+    ("Host", "example.com"),
+    (
+        "User-Agent",
+        "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36",
+    ),
+    (
+        "Accept",
+        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+    ),
+    ("Accept-Language", "en-US,en;q=0.5"),
+    ("Accept-Encoding", "gzip, deflate, br"),
+    ("Connection", "keep-alive"),
+    (
+        "Cookie",
+        "session=8f14e45fceea167a5a36dedd4bea2543; theme=dark",
+    ),
+    ("Cache-Control", "no-cache"),
+    ("Referer", "https://example.com/search?q=rust"),
+    ("X-Request-Id", "c0a8012e-5d3b-4a7f-9e21-6b8f0d4c3a19"),
+];
+
+// A seeded, repeatable stream of realistic requests for benchmarks and load tests.
+pub struct RequestGenerator {
+    state: u64,
+    max_headers: usize,
+    max_body_size: usize,
+}
+
+impl RequestGenerator {
+    pub fn new(seed: u64) -> Self {
+        RequestGenerator {
+            state: seed | 1,
+            max_headers: HEADERS.len(),
+            max_body_size: 512,
+        }
+    }
+
+    pub fn max_headers(mut self, count: usize) -> Self {
+        self.max_headers = count.min(HEADERS.len());
+        self
+    }
+
+    // Only POST, PUT and PATCH requests carry a body.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    // xorshift64*, which is plenty for picking among fixtures.
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            0
+        } else {
+            (self.next_u64() % bound as u64) as usize
+        }
+    }
+
+    pub fn next_request(&mut self) -> Request {
+        let method = match self.below(10) {
+            0..=5 => Method::GET,
+            6 => Method::POST,
+            7 => Method::PUT,
+            8 => Method::PATCH,
+            _ => Method::DELETE,
+        };
+        let path = PATHS[self.below(PATHS.len())].to_string();
+        let query_string = match self.below(3) {
+            0 => Some(format!(
+                "q={}&page={}",
+                self.next_u64() % 10_000,
+                self.below(50)
+            )),
+            _ => None,
+        };
+
+        let mut headers = Headers::new();
+        let count = 1 + self.below(self.max_headers);
+        for (name, value) in HEADERS.iter().take(count) {
+            headers.append(name, value);
+        }
+
+        let body = match method {
+            Method::POST | Method::PUT | Method::PATCH => {
+                let size = self.below(self.max_body_size + 1);
+                headers.append("Content-Type", "application/json");
+                (0..size).map(|i| b"{\"key\": \"value\"}"[i % 16]).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        Request {
+            path,
+            query_string,
+            method,
+            headers,
+            body,
+            remote_addr: None,
+            extensions: Extensions::new(),
+        }
+    }
+}
+
+impl Iterator for RequestGenerator {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        Some(self.next_request().to_bytes())
+    }
+}
@@ -1,6 +1,10 @@
+use super::borrowed::is_token_byte;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     GET,
     DELETE,
@@ -11,6 +15,45 @@ pub enum Method {
     OPTIONS,
     TRACE,
     PATCH,
+    // Any other valid token, kept as sent, e.g. WebDAV's PROPFIND or MKCOL. Methods are
+    // case-sensitive, so "get" is an extension too.
+    Extension(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::GET => "GET",
+            Method::DELETE => "DELETE",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::HEAD => "HEAD",
+            Method::CONNECT => "CONNECT",
+            Method::OPTIONS => "OPTIONS",
+            Method::TRACE => "TRACE",
+            Method::PATCH => "PATCH",
+            Method::Extension(token) => token,
+        }
+    }
+
+    // Safe to send again when unsure whether the first attempt arrived (RFC 9110, 9.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Method::GET
+                | Method::HEAD
+                | Method::PUT
+                | Method::DELETE
+                | Method::OPTIONS
+                | Method::TRACE
+        )
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 impl FromStr for Method {
@@ -27,11 +70,34 @@ impl FromStr for Method {
             "OPTIONS" => Ok(Method::OPTIONS),
             "TRACE" => Ok(Method::TRACE),
             "PATCH" => Ok(Method::PATCH),
-            _ => Err(MethodError { message: String::new() }),
+            _ if is_token(s) => Ok(Method::Extension(s.to_string())),
+            _ => Err(MethodError {
+                token: s.to_string(),
+            }),
         }
     }
 }
 
-pub struct MethodError{
-    message: String
-}
\ No newline at end of file
+// RFC 9110 section 5.6.2: one or more tchars.
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_token_byte)
+}
+
+#[derive(Debug)]
+pub struct MethodError {
+    token: String,
+}
+
+impl MethodError {
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl Display for MethodError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "invalid method {:?}", self.token)
+    }
+}
+
+impl Error for MethodError {}
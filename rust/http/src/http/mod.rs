@@ -1,6 +1,19 @@
-pub use request::Request;
+pub use extensions::Extensions;
+pub use generator::RequestGenerator;
+pub use headers::Headers;
 pub use method::Method;
 pub use request::ParseError;
+pub use request::Request;
+pub use response::{Body, Framing, Response};
+pub use status_code::StatusCode;
 
+pub mod borrowed;
+pub mod chunked;
+pub mod extensions;
+pub mod generator;
+pub mod headers;
+pub mod method;
+pub mod path;
 pub mod request;
-pub mod method;
\ No newline at end of file
+pub mod response;
+pub mod status_code;
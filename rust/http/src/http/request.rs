@@ -1,86 +1,72 @@
-use super::method::{self, MethodError};
-use crate::http::Method;
+use super::borrowed;
+use super::method::MethodError;
+use super::{Extensions, Headers};
+use crate::http::{Method, StatusCode};
 use std::convert::TryFrom;
 use std::error::Error;
-use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
-use std::str::{self, FromStr, Utf8Error};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::net::SocketAddr;
+use std::str::{self, Utf8Error};
 
 #[derive(Debug)]
 pub struct Request {
     pub path: String,
     pub query_string: Option<String>,
-    pub method: method::Method,
+    pub method: Method,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    pub remote_addr: Option<SocketAddr>,
+    pub extensions: Extensions,
 }
 
 impl Request {
-    fn from_byte_array(buffer: &[u8]) -> Result<Self, ParseError> {
-        Ok::<Self, ParseError>(Self {
-            path: String::from(""),
-            query_string: None,
-            method: method::Method::GET,
-        })
+    pub fn wants_keep_alive(&self) -> bool {
+        match self.headers.get("Connection") {
+            Some(value) => !value.eq_ignore_ascii_case("close"),
+            None => true,
+        }
     }
-}
-
-impl TryFrom<&[u8]> for Request {
-    type Error = ParseError;
-
-    // GET /search?name=abc&sort=1 HTTP/1.1
-    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
-        let request = str::from_utf8(buffer)?;
-
-        let (method, request) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
-        let (mut path, request) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
-        let (protocol, _) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
 
-        if protocol != "HTTP/1.1" {
-            return Err(ParseError::InvalidProtocol);
+    // The request as it would be sent; Content-Length is added for a body without one.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("{} {}", self.method, self.path);
+        if let Some(query_string) = &self.query_string {
+            head.push('?');
+            head.push_str(query_string);
         }
-
-        let method: Method = method.parse()?;
-        let mut query_string = None;
-        if let Some(i) = path.find('?') {
-            query_string = Some(&path[i + 1..]);
-            path = &path[..i];
+        head.push_str(" HTTP/1.1\r\n");
+        for (name, value) in self.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
         }
-
-        let _ = Ok::<Self, ParseError>(Self {
-            path: path.to_string(),
-            query_string: query_string.map(|qs| qs.to_string()),
-            method,
-        });
-
-        unimplemented!()
-    }
-}
-
-fn get_next_word(request: &str) -> Option<(&str, &str)> {
-    request.chars().enumerate().find_map(|(i, c)| {
-        if c == ' ' || c == '\r' {
-            Some((&request[..i], &request[i + 1..]))
-        } else {
-            None
+        if !self.body.is_empty() && !self.headers.contains("Content-Length") {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
         }
-    })
-}
+        head.push_str("\r\n");
 
-impl Display for ParseError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{:?}", self)
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
     }
 }
 
-impl Debug for ParseError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{:?}", self)
+impl TryFrom<&[u8]> for Request {
+    type Error = ParseError;
+
+    // GET /search?name=abc&sort=1 HTTP/1.1
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        borrowed::Request::parse(buffer).map(borrowed::Request::into_owned)
     }
 }
 
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
 pub enum ParseError {
     InvalidRequest,
     InvalidEncoding,
     InvalidProtocol,
     InvalidMethod,
+    InvalidHeader,
+    UnsupportedTransferEncoding,
 }
 
 impl ParseError {
@@ -90,10 +76,29 @@ impl ParseError {
             Self::InvalidEncoding => "Invalid Encoding",
             Self::InvalidProtocol => "Invalid Protocol",
             Self::InvalidMethod => "Invalid Method",
+            Self::InvalidHeader => "Invalid Header",
+            Self::UnsupportedTransferEncoding => "Unsupported Transfer-Encoding",
         }
     }
+
+    // A method that is not a valid token, or a transfer coding the server cannot decode, is
+    // 501 Not Implemented; anything else is 400.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidMethod | Self::UnsupportedTransferEncoding => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.message())
+    }
 }
 
+impl Error for ParseError {}
+
 impl From<Utf8Error> for ParseError {
     fn from(_: Utf8Error) -> Self {
         Self::InvalidEncoding
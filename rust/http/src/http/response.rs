@@ -0,0 +1,315 @@
+use super::chunked::{self, ChunkedReader};
+use super::{Headers, Method, StatusCode};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io::{
+    self, BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write,
+};
+use std::net::TcpStream;
+
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+// More interim responses than this before the final one is treated as a broken peer.
+const MAX_INTERIM_RESPONSES: usize = 16;
+
+pub enum Body {
+    Bytes(Vec<u8>),
+    // Streamed to the client; without a known length it is sent chunked.
+    Reader {
+        reader: Box<dyn Read + Send>,
+        length: Option<u64>,
+    },
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Body::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Body::Reader { length, .. } => write!(f, "Reader({:?})", length),
+        }
+    }
+}
+
+// Takes over the connection once a `101 Switching Protocols` response has been sent.
+pub struct Upgrade(Box<dyn FnOnce(BufReader<TcpStream>, TcpStream) + Send>);
+
+impl Upgrade {
+    pub fn run(self, reader: BufReader<TcpStream>, stream: TcpStream) {
+        (self.0)(reader, stream)
+    }
+}
+
+impl Debug for Upgrade {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Upgrade")
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    status_code: StatusCode,
+    pub headers: Headers,
+    body: Body,
+    upgrade: Option<Upgrade>,
+}
+
+impl Response {
+    pub fn new(status_code: StatusCode) -> Self {
+        Response {
+            status_code,
+            headers: Headers::new(),
+            body: Body::Bytes(Vec::new()),
+            upgrade: None,
+        }
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Body::Bytes(body.into());
+        self
+    }
+
+    pub fn with_reader(mut self, reader: impl Read + Send + 'static, length: Option<u64>) -> Self {
+        self.body = Body::Reader {
+            reader: Box::new(reader),
+            length,
+        };
+        self
+    }
+
+    pub fn with_upgrade(
+        mut self,
+        upgrade: impl FnOnce(BufReader<TcpStream>, TcpStream) + Send + 'static,
+    ) -> Self {
+        self.upgrade = Some(Upgrade(Box::new(upgrade)));
+        self
+    }
+
+    pub fn take_upgrade(&mut self) -> Option<Upgrade> {
+        self.upgrade.take()
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    // None while the body is still an unread stream.
+    pub fn body(&self) -> Option<&[u8]> {
+        match &self.body {
+            Body::Bytes(bytes) => Some(bytes),
+            Body::Reader { .. } => None,
+        }
+    }
+
+    pub fn into_body(self) -> Body {
+        self.body
+    }
+
+    // Reads a streamed body to the end so it can be inspected with `body()`.
+    pub fn buffer_body(&mut self) -> IoResult<()> {
+        if let Body::Reader { reader, .. } = &mut self.body {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            self.body = Body::Bytes(bytes);
+        }
+        Ok(())
+    }
+
+    pub fn send(self, stream: &mut impl Write) -> IoResult<()> {
+        let length = self.body_length();
+        let mut head = self.head(length);
+        match self.body {
+            Body::Bytes(bytes) => {
+                head.extend_from_slice(&bytes);
+                stream.write_all(&head)?;
+            }
+            Body::Reader { mut reader, .. } => {
+                stream.write_all(&head)?;
+                match length {
+                    Some(length) => {
+                        let copied = io::copy(&mut reader.take(length), stream)?;
+                        if copied < length {
+                            return Err(IoError::new(
+                                ErrorKind::UnexpectedEof,
+                                "response body shorter than its length",
+                            ));
+                        }
+                    }
+                    None => {
+                        chunked::copy_chunked(&mut reader, stream)?;
+                    }
+                }
+            }
+        }
+        stream.flush()
+    }
+
+    // Sends the status line and headers only, as required for HEAD requests.
+    pub fn send_head(&self, stream: &mut impl Write) -> IoResult<()> {
+        stream.write_all(&self.head(self.body_length()))?;
+        stream.flush()
+    }
+
+    fn body_length(&self) -> Option<u64> {
+        match &self.body {
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::Reader { length, .. } => *length,
+        }
+    }
+
+    fn head(&self, length: Option<u64>) -> Vec<u8> {
+        let mut head = String::new();
+        head.push_str(&format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status_code,
+            self.status_code.reason_phrase()
+        ));
+        for (name, value) in self.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        // These statuses never carry a body, so no length describes one.
+        let has_body = self.status_code.as_u16() >= 200
+            && self.status_code != StatusCode::NO_CONTENT
+            && self.status_code != StatusCode::NOT_MODIFIED;
+        if has_body && !self.headers.contains("Content-Length") {
+            match length {
+                Some(length) => head.push_str(&format!("Content-Length: {}\r\n", length)),
+                None => head.push_str("Transfer-Encoding: chunked\r\n"),
+            }
+        }
+        head.push_str("\r\n");
+        head.into_bytes()
+    }
+
+    // Parses a response read from another server, leaving the body as a stream over `reader`.
+    pub fn read_from(
+        mut reader: impl BufRead + Send + 'static,
+        method: &Method,
+    ) -> IoResult<Response> {
+        let (status_code, mut headers) = read_final_head(&mut reader)?;
+        let response = Response::new(status_code);
+        let response = match Framing::of(method, status_code, &mut headers)? {
+            Framing::Empty => response,
+            Framing::Chunked => response.with_reader(ChunkedReader::new(reader), None),
+            Framing::Length(length) => response.with_reader(reader.take(length), Some(length)),
+            Framing::UntilClose => response.with_reader(reader, None),
+        };
+        Ok(Response {
+            headers,
+            ..response
+        })
+    }
+}
+
+// How the body of a received response is delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    Empty,
+    Length(u64),
+    Chunked,
+    UntilClose,
+}
+
+impl Framing {
+    // Strips the framing headers from `headers`, except for HEAD responses where they
+    // describe the body that was not sent.
+    pub fn of(method: &Method, status_code: StatusCode, headers: &mut Headers) -> IoResult<Self> {
+        if *method == Method::HEAD
+            || status_code.as_u16() < 200
+            || status_code == StatusCode::NO_CONTENT
+            || status_code == StatusCode::NOT_MODIFIED
+        {
+            return Ok(Framing::Empty);
+        }
+
+        let chunked = headers
+            .get("Transfer-Encoding")
+            .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+        let length = match headers.get("Content-Length") {
+            Some(value) => Some(
+                value
+                    .parse::<u64>()
+                    .map_err(|_| IoError::new(ErrorKind::InvalidData, "invalid Content-Length"))?,
+            ),
+            None => None,
+        };
+        headers.remove("Transfer-Encoding");
+        headers.remove("Content-Length");
+
+        Ok(match (chunked, length) {
+            (true, _) => Framing::Chunked,
+            (false, Some(length)) => Framing::Length(length),
+            (false, None) => Framing::UntilClose,
+        })
+    }
+}
+
+// Like `read_head`, but skips interim 1xx responses such as 100 Continue. 101 Switching
+// Protocols is final, as the connection changes protocol after it.
+pub fn read_final_head(reader: &mut impl BufRead) -> IoResult<(StatusCode, Headers)> {
+    for _ in 0..=MAX_INTERIM_RESPONSES {
+        let (status_code, headers) = read_head(reader)?;
+        if status_code.as_u16() >= 200 || status_code == StatusCode::SWITCHING_PROTOCOLS {
+            return Ok((status_code, headers));
+        }
+    }
+    Err(IoError::new(
+        ErrorKind::InvalidData,
+        "too many interim responses",
+    ))
+}
+
+// Reads a status line and headers, leaving `reader` at the start of the body.
+pub fn read_head(reader: &mut impl BufRead) -> IoResult<(StatusCode, Headers)> {
+    let mut lines = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take((MAX_HEAD_SIZE + 1 - size) as u64)
+            .read_line(&mut line)?;
+        if read == 0 {
+            return Err(IoError::new(
+                ErrorKind::UnexpectedEof,
+                "incomplete response head",
+            ));
+        }
+        size += read;
+        if size > MAX_HEAD_SIZE {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "response head too large",
+            ));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line.to_string());
+    }
+
+    let invalid = || IoError::new(ErrorKind::InvalidData, "invalid response head");
+    let (status_line, header_lines) = lines.split_first().ok_or_else(invalid)?;
+    let mut parts = status_line.splitn(3, ' ');
+    if !parts
+        .next()
+        .is_some_and(|protocol| protocol.starts_with("HTTP/1."))
+    {
+        return Err(invalid());
+    }
+    let code = parts
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .filter(|code| (100..1000).contains(code))
+        .ok_or_else(invalid)?;
+
+    let mut headers = Headers::new();
+    for line in header_lines {
+        let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+        headers.append(name.trim(), value.trim());
+    }
+    Ok((StatusCode(code), headers))
+}
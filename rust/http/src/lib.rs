@@ -0,0 +1,21 @@
+pub mod admin;
+#[cfg(feature = "async")]
+pub mod async_server;
+pub mod client;
+pub mod config;
+pub mod connections;
+pub mod handlers;
+pub mod har;
+pub mod http;
+pub mod metrics;
+pub mod middleware;
+pub mod replay;
+pub mod router;
+pub mod server;
+pub mod sse;
+pub mod templates;
+pub mod thread_pool;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod virtual_hosts;
+pub mod websocket;
@@ -1,23 +1,194 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
+mod cli;
 
-mod http;
-mod server;
-
-use http::Method;
-use http::Request;
-use server::Server;
+use cli::{Command, Options, ReplayOptions};
+use http::admin;
+use http::config::{Config, ConfigError};
+use http::handlers::{Cgi, Proxy, StaticFiles};
+use http::har::Har;
+use http::http::{Request, Response, StatusCode};
+use http::metrics::Metrics;
+use http::middleware::Recorder;
+use http::replay::{Outcome, Replay};
+use http::router::Router;
+use http::server::{Server, ServerHandle};
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Duration;
 
 fn main() {
-    let ip_address: String = "127.0.0.1".to_string();
-    let port: u32 = 8080;
-    let mut server: Server = Server::new(ip_address, port);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Command::parse(&args) {
+        Ok(Command::Serve(options)) => options,
+        Ok(Command::Replay(options)) => process::exit(replay(&options)),
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let config = match load_config(&options) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            process::exit(1);
+        }
+    };
+    if options.check_config {
+        println!("Configuration OK");
+        return;
+    }
+
+    let metrics = config.metrics_path.as_ref().map(|_| Metrics::new());
+    let mut server: Server = config.server();
+    #[cfg(feature = "tls")]
+    match config.tls() {
+        Ok(Some(tls)) => server = server.tls(tls),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            process::exit(1);
+        }
+    }
+    if let Some(metrics) = &metrics {
+        server = server.metrics(metrics.clone());
+    }
+    let router = build_router(&config, server.handle());
+
+    if let Some(mut admin_server) = config.admin_server() {
+        let admin_router = admin::router(config.clone(), server.handle(), metrics);
+        thread::spawn(move || {
+            if let Err(e) = admin_server.run(admin_router) {
+                eprintln!("Failed to start admin server: {}", e);
+            }
+        });
+    }
 
     println!(
         "Server is starting... on {}:{}",
         server.ip_address, server.port
     );
 
-    server.run();
+    let result = match config.runtime {
+        #[cfg(feature = "async")]
+        http::config::Runtime::Async => server.run_async(router),
+        _ => server.run(router),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to start server: {}", e);
+        process::exit(1);
+    }
     println!("Exiting server...");
 }
+
+// Defaults, then the config file, then HTTP_* variables, then flags.
+fn load_config(options: &Options) -> Result<Config, ConfigError> {
+    let path = options
+        .config
+        .clone()
+        .or_else(|| env::var_os("HTTP_CONFIG").map(PathBuf::from));
+    let mut config = match path {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    config.apply_env(env::vars())?;
+    for (setting, value) in &options.overrides {
+        config.set(setting, value)?;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+// Returns the exit code: 0 when every response matches the recording.
+fn replay(options: &ReplayOptions) -> i32 {
+    let har = match Har::load(&options.har) {
+        Ok(har) => har,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", options.har.display(), e);
+            return 2;
+        }
+    };
+    let mut replay = match Replay::new(&options.target) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    for name in &options.ignored_headers {
+        replay = replay.ignore_header(name);
+    }
+
+    let mut failures = 0;
+    for (i, entry) in har.log.entries.iter().enumerate() {
+        let label = format!("[{}] {} {}", i + 1, entry.request.method, entry.request.url);
+        match replay.run(entry) {
+            Outcome::Matched => println!("{}: ok", label),
+            Outcome::Skipped(reason) => println!("{}: skipped, {}", label, reason),
+            Outcome::Failed(e) => {
+                failures += 1;
+                println!("{}: failed, {}", label, e);
+            }
+            Outcome::Differs(differences) => {
+                failures += 1;
+                println!("{}: differs", label);
+                for difference in differences {
+                    println!("    {}", difference);
+                }
+            }
+        }
+    }
+    println!(
+        "{} of {} entries replayed identically",
+        har.log.entries.len() - failures,
+        har.log.entries.len()
+    );
+    i32::from(failures > 0)
+}
+
+fn build_router(config: &Config, server: ServerHandle) -> Router {
+    let mut router = Router::new();
+    if let Some(path) = &config.record_har {
+        router = router.with(Recorder::new().save_to(path));
+    }
+    if let Some(rate_limit) = config.rate_limiter() {
+        router = router.with(rate_limit);
+    }
+
+    if config.health_checks {
+        router = router
+            .get("/healthz", admin::healthz)
+            .get("/readyz", admin::Readiness::new(server));
+    }
+
+    for mount in &config.mounts {
+        let pattern = format!("{}/*", mount.path.trim_end_matches('/'));
+        router = match (&mount.static_root, &mount.cgi) {
+            (Some(root), _) => {
+                router.any(&pattern, StaticFiles::new(root).strip_prefix(&mount.path))
+            }
+            (None, Some(script)) => router.any(
+                &pattern,
+                Cgi::new(script)
+                    .strip_prefix(&mount.path)
+                    .timeout(Duration::from_secs(mount.timeout)),
+            ),
+            (None, None) => {
+                let upstreams: Vec<&str> = mount.proxy.iter().map(String::as_str).collect();
+                router.any(&pattern, Proxy::new(&upstreams).strip_prefix(&mount.path))
+            }
+        };
+    }
+
+    match &config.static_root {
+        Some(root) => router.any("/*", StaticFiles::new(root)),
+        None => router.get("/", |_: &mut Request| {
+            Response::new(StatusCode::OK).with_body("Hello from the http server")
+        }),
+    }
+}
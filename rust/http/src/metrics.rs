@@ -0,0 +1,244 @@
+use crate::http::{Method, ParseError, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{Result as IoResult, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Upper bounds in seconds; the implicit +Inf bucket is the request count.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Requests that no route matched share one label so stray paths cannot grow the series.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    latency: Mutex<Histogram>,
+    parse_errors: Mutex<BTreeMap<String, u64>>,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    active_connections: AtomicUsize,
+    connections_total: AtomicU64,
+}
+
+// Shared counters the server updates; serving it renders the Prometheus text format.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connection_opened(&self) -> ConnectionMetric {
+        self.inner
+            .active_connections
+            .fetch_add(1, Ordering::Relaxed);
+        self.inner.connections_total.fetch_add(1, Ordering::Relaxed);
+        ConnectionMetric {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    pub fn record_request(
+        &self,
+        method: &Method,
+        route: &str,
+        status: StatusCode,
+        elapsed: Duration,
+    ) {
+        // Extension methods share one label, as clients can send any number of distinct tokens.
+        let method = match method {
+            Method::Extension(_) => "OTHER",
+            method => method.as_str(),
+        };
+        let key = (method.to_string(), route.to_string(), status.as_u16());
+        *self.inner.requests.lock().unwrap().entry(key).or_insert(0) += 1;
+        self.inner
+            .latency
+            .lock()
+            .unwrap()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_parse_error(&self, e: &ParseError) {
+        *self
+            .inner
+            .parse_errors
+            .lock()
+            .unwrap()
+            .entry(format!("{:?}", e))
+            .or_insert(0) += 1;
+    }
+
+    pub fn add_bytes_received(&self, count: u64) {
+        self.inner
+            .bytes_received
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_sent(&self, count: u64) {
+        self.inner.bytes_sent.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Requests handled, by method, route and status.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((method, route, status), count) in self.inner.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(method),
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        out.push_str(
+            "# HELP http_request_duration_seconds Time from parsed request to sent response.\n",
+        );
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        {
+            let latency = self.inner.latency.lock().unwrap();
+            for (count, bound) in latency.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                    bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+                latency.count
+            );
+            let _ = writeln!(out, "http_request_duration_seconds_sum {}", latency.sum);
+            let _ = writeln!(out, "http_request_duration_seconds_count {}", latency.count);
+        }
+
+        out.push_str("# HELP http_parse_errors_total Requests rejected by the parser, by error.\n");
+        out.push_str("# TYPE http_parse_errors_total counter\n");
+        for (error, count) in self.inner.parse_errors.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "http_parse_errors_total{{error=\"{}\"}} {}",
+                escape(error),
+                count
+            );
+        }
+
+        let counters = [
+            (
+                "http_received_bytes_total",
+                "counter",
+                "Request bytes read, heads included.",
+                self.inner.bytes_received.load(Ordering::Relaxed),
+            ),
+            (
+                "http_sent_bytes_total",
+                "counter",
+                "Response bytes written, heads included.",
+                self.inner.bytes_sent.load(Ordering::Relaxed),
+            ),
+            (
+                "http_connections_total",
+                "counter",
+                "Connections accepted.",
+                self.inner.connections_total.load(Ordering::Relaxed),
+            ),
+            (
+                "http_active_connections",
+                "gauge",
+                "Connections currently open.",
+                self.inner.active_connections.load(Ordering::Relaxed) as u64,
+            ),
+        ];
+        for (name, kind, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+impl Handler for Metrics {
+    fn handle_request(&self, _request: &mut Request) -> Response {
+        Response::new(StatusCode::OK)
+            .with_header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+            .with_body(self.render())
+    }
+}
+
+// Keeps the active connection gauge up while a connection is being served.
+pub struct ConnectionMetric {
+    inner: Arc<Inner>,
+}
+
+impl Drop for ConnectionMetric {
+    fn drop(&mut self) {
+        self.inner
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Counts what passes through to the socket so streamed bodies are measured too.
+pub struct CountingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    pub written: u64,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        CountingWriter { inner, written: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
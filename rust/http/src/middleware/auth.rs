@@ -0,0 +1,250 @@
+use super::{Middleware, Next};
+use crate::http::path::{is_under, normalize};
+use crate::http::{Request, Response, StatusCode};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Basic,
+    Bearer,
+}
+
+pub enum Credentials {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+
+impl Credentials {
+    pub fn scheme(&self) -> Scheme {
+        match self {
+            Credentials::Basic { .. } => Scheme::Basic,
+            Credentials::Bearer { .. } => Scheme::Bearer,
+        }
+    }
+
+    // Parses an `Authorization` header value; unknown schemes and malformed values give None.
+    pub fn parse(header: &str) -> Option<Self> {
+        let (scheme, value) = header.trim().split_once(' ')?;
+        let value = value.trim();
+        if scheme.eq_ignore_ascii_case("Basic") {
+            let decoded = STANDARD.decode(value).ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            Some(Credentials::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            })
+        } else if scheme.eq_ignore_ascii_case("Bearer") && !value.is_empty() {
+            Some(Credentials::Bearer {
+                token: value.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+// The authenticated caller, stored in `Request::extensions` for handlers to read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub scheme: Scheme,
+}
+
+pub trait CredentialChecker: Send + Sync {
+    fn check(&self, credentials: &Credentials) -> Option<Principal>;
+}
+
+impl<F> CredentialChecker for F
+where
+    F: Fn(&Credentials) -> Option<Principal> + Send + Sync,
+{
+    fn check(&self, credentials: &Credentials) -> Option<Principal> {
+        self(credentials)
+    }
+}
+
+// Basic credentials from an htpasswd-style file with `user:$sha256$<salt>$<digest>` lines,
+// where salt and digest are base64 and the digest is SHA-256 over salt followed by password.
+pub struct HtpasswdFile {
+    users: HashMap<String, (Vec<u8>, Vec<u8>)>,
+}
+
+impl HtpasswdFile {
+    pub fn load(path: impl AsRef<Path>) -> IoResult<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> IoResult<Self> {
+        let mut users = HashMap::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = line.split_once(':').and_then(|(user, hash)| {
+                let hash = hash.strip_prefix("$sha256$")?;
+                let (salt, digest) = hash.split_once('$')?;
+                Some((
+                    user.to_string(),
+                    (STANDARD.decode(salt).ok()?, STANDARD.decode(digest).ok()?),
+                ))
+            });
+            match entry {
+                Some((user, hash)) => {
+                    users.insert(user, hash);
+                }
+                None => {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("invalid htpasswd entry on line {}", number + 1),
+                    ))
+                }
+            }
+        }
+        Ok(HtpasswdFile { users })
+    }
+
+    // Formats the hash part of an entry, for writing new users to the file.
+    pub fn hash_password(password: &str, salt: &[u8]) -> String {
+        format!(
+            "$sha256${}${}",
+            STANDARD.encode(salt),
+            STANDARD.encode(digest(salt, password))
+        )
+    }
+}
+
+impl CredentialChecker for HtpasswdFile {
+    fn check(&self, credentials: &Credentials) -> Option<Principal> {
+        let (username, password) = match credentials {
+            Credentials::Basic { username, password } => (username, password),
+            Credentials::Bearer { .. } => return None,
+        };
+        let (salt, expected) = self.users.get(username)?;
+        if constant_time_eq(&digest(salt, password), expected) {
+            Some(Principal {
+                name: username.clone(),
+                scheme: Scheme::Basic,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn digest(salt: &[u8], password: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(password.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub struct Auth {
+    checker: Box<dyn CredentialChecker>,
+    realm: String,
+    schemes: Vec<Scheme>,
+    protected: Vec<String>,
+}
+
+impl Auth {
+    pub fn new(realm: &str, checker: impl CredentialChecker + 'static) -> Self {
+        Auth {
+            checker: Box::new(checker),
+            realm: realm.to_string(),
+            schemes: vec![Scheme::Basic, Scheme::Bearer],
+            protected: Vec::new(),
+        }
+    }
+
+    pub fn schemes(mut self, schemes: &[Scheme]) -> Self {
+        self.schemes = schemes.to_vec();
+        self
+    }
+
+    // Only paths under the given prefixes require credentials; with none, every path does.
+    // "/admin" covers "/admin" and "/admin/users" but not "/administrator".
+    pub fn protect(mut self, prefix: &str) -> Self {
+        self.protected.push(prefix.to_string());
+        self
+    }
+
+    // Compares the decoded, normalized path, as handlers such as `StaticFiles` serve
+    // "/%61dmin/x" from "/admin/x". Paths that cannot be normalized are protected rather
+    // than guessed at.
+    fn is_protected(&self, path: &str) -> bool {
+        if self.protected.is_empty() {
+            return true;
+        }
+        match normalize(path) {
+            Some(path) => self.protected.iter().any(|prefix| is_under(&path, prefix)),
+            None => true,
+        }
+    }
+
+    pub fn authenticate(&self, request: &Request) -> Result<Principal, Response> {
+        let credentials = match request.headers.get("Authorization") {
+            Some(header) => Credentials::parse(header)
+                .filter(|credentials| self.schemes.contains(&credentials.scheme()))
+                .ok_or_else(|| self.challenge(Some("invalid_request")))?,
+            None => return Err(self.challenge(None)),
+        };
+        self.checker
+            .check(&credentials)
+            .ok_or_else(|| match credentials.scheme() {
+                Scheme::Bearer => self.challenge(Some("invalid_token")),
+                Scheme::Basic => self.challenge(None),
+            })
+    }
+
+    fn challenge(&self, bearer_error: Option<&str>) -> Response {
+        let mut response =
+            Response::new(StatusCode::UNAUTHORIZED).with_body("Authentication required");
+        for scheme in &self.schemes {
+            let challenge = match (scheme, bearer_error) {
+                (Scheme::Basic, _) => {
+                    format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm)
+                }
+                (Scheme::Bearer, Some(error)) => {
+                    format!("Bearer realm=\"{}\", error=\"{}\"", self.realm, error)
+                }
+                (Scheme::Bearer, None) => format!("Bearer realm=\"{}\"", self.realm),
+            };
+            response.headers.append("WWW-Authenticate", &challenge);
+        }
+        response
+    }
+}
+
+impl Middleware for Auth {
+    fn handle(&self, request: &mut Request, next: Next) -> Response {
+        if !self.is_protected(&request.path) {
+            return next.run(request);
+        }
+        match self.authenticate(request) {
+            Ok(principal) => {
+                request.extensions.insert(principal);
+                next.run(request)
+            }
+            Err(response) => response,
+        }
+    }
+
+    fn check_head(&self, request: &mut Request) -> Option<Response> {
+        if !self.is_protected(&request.path) {
+            return None;
+        }
+        self.authenticate(request).err()
+    }
+}
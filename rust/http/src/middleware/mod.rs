@@ -0,0 +1,42 @@
+use crate::http::{Request, Response};
+use crate::server::Handler;
+
+pub use auth::{Auth, CredentialChecker, Credentials, HtpasswdFile, Principal, Scheme};
+pub use rate_limit::{KeyBy, Rate, RateLimit};
+pub use recorder::{Recorder, Recording};
+
+pub mod auth;
+pub mod rate_limit;
+pub mod recorder;
+
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: &mut Request, next: Next) -> Response;
+
+    // Like `Handler::check_head`: lets the middleware turn away an upload before its body
+    // is read. Only checks that need no body belong here.
+    fn check_head(&self, _request: &mut Request) -> Option<Response> {
+        None
+    }
+}
+
+// The rest of the middleware chain, ending in the handler the request was routed to.
+pub struct Next<'a> {
+    middleware: &'a [Box<dyn Middleware>],
+    endpoint: &'a dyn Handler,
+}
+
+impl<'a> Next<'a> {
+    pub fn new(middleware: &'a [Box<dyn Middleware>], endpoint: &'a dyn Handler) -> Self {
+        Next {
+            middleware,
+            endpoint,
+        }
+    }
+
+    pub fn run(self, request: &mut Request) -> Response {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(request, Next::new(rest, self.endpoint)),
+            None => self.endpoint.handle_request(request),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use super::{Middleware, Next};
+use crate::http::path::{is_under, normalize};
+use crate::http::{Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+pub enum KeyBy {
+    RemoteAddr,
+    // Falls back to the remote address when the header is missing.
+    Header(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rate {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl Rate {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Rate { per_second, burst }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    last_seen: Instant,
+}
+
+impl TokenBucket {
+    fn full(rate: &Rate, now: Instant) -> Self {
+        TokenBucket {
+            tokens: rate.burst as f64,
+            last_refill: now,
+            last_seen: now,
+        }
+    }
+
+    fn refill(&mut self, rate: &Rate, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst as f64);
+        self.last_refill = now;
+    }
+
+    // Takes a token, or returns how many seconds until one is available.
+    fn try_take(&mut self, rate: &Rate, now: Instant) -> Result<(), f64> {
+        self.refill(rate, now);
+        self.last_seen = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if rate.per_second > 0.0 {
+            Err((1.0 - self.tokens) / rate.per_second)
+        } else {
+            Err(f64::INFINITY)
+        }
+    }
+}
+
+pub struct RateLimit {
+    key_by: KeyBy,
+    default_rate: Option<Rate>,
+    routes: Vec<(String, Rate)>,
+    buckets: Mutex<HashMap<(usize, String), TokenBucket>>,
+}
+
+impl RateLimit {
+    pub fn new(key_by: KeyBy) -> Self {
+        RateLimit {
+            key_by,
+            default_rate: None,
+            routes: Vec::new(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn default_rate(mut self, rate: Rate) -> Self {
+        self.default_rate = Some(rate);
+        self
+    }
+
+    // Applies `rate` to `prefix` and every path below it; the longest prefix wins.
+    pub fn route(mut self, prefix: &str, rate: Rate) -> Self {
+        self.routes.push((prefix.to_string(), rate));
+        self
+    }
+
+    // Buckets are kept per rule, so index `routes.len()` stands for the default rate.
+    fn rate_for(&self, path: &str) -> Option<(usize, Rate)> {
+        self.routes
+            .iter()
+            .enumerate()
+            .filter(|(_, (prefix, _))| is_under(path, prefix))
+            .max_by_key(|(_, (prefix, _))| prefix.len())
+            .map(|(i, (_, rate))| (i, *rate))
+            .or_else(|| self.default_rate.map(|rate| (self.routes.len(), rate)))
+    }
+
+    fn key_for(&self, request: &Request) -> String {
+        if let KeyBy::Header(name) = &self.key_by {
+            if let Some(value) = request.headers.get(name) {
+                return format!("{}:{}", name, value);
+            }
+        }
+        match request.remote_addr {
+            Some(addr) => addr.ip().to_string(),
+            None => String::from("unknown"),
+        }
+    }
+
+    // Drops fully refilled buckets, as they behave like new ones. When clients keep that many
+    // buckets partly drained, the least recently seen tenth is evicted too, so the map stays
+    // bounded and pruning does not run again on the very next request.
+    fn prune(&self, buckets: &mut HashMap<(usize, String), TokenBucket>, now: Instant) {
+        buckets.retain(|(rule, _), bucket| {
+            let rate = self
+                .routes
+                .get(*rule)
+                .map(|(_, rate)| *rate)
+                .or(self.default_rate);
+            match rate {
+                Some(rate) => {
+                    bucket.refill(&rate, now);
+                    bucket.tokens < rate.burst as f64
+                }
+                None => false,
+            }
+        });
+
+        let keep = MAX_TRACKED_BUCKETS - MAX_TRACKED_BUCKETS / 10;
+        if buckets.len() > keep {
+            let mut seen: Vec<Instant> = buckets.values().map(|bucket| bucket.last_seen).collect();
+            let excess = buckets.len() - keep;
+            let (_, &mut cutoff, _) = seen.select_nth_unstable(excess - 1);
+            buckets.retain(|_, bucket| bucket.last_seen > cutoff);
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn handle(&self, request: &mut Request, next: Next) -> Response {
+        // Matched decoded, so "/%61pi" counts against "/api".
+        let path = normalize(&request.path).unwrap_or_else(|| request.path.clone());
+        let (rule, rate) = match self.rate_for(&path) {
+            Some(rule) => rule,
+            None => return next.run(request),
+        };
+        let key = self.key_for(request);
+        let now = Instant::now();
+
+        let result = {
+            let mut buckets = self.buckets.lock().unwrap();
+            if buckets.len() >= MAX_TRACKED_BUCKETS {
+                self.prune(&mut buckets, now);
+            }
+            buckets
+                .entry((rule, key))
+                .or_insert_with(|| TokenBucket::full(&rate, now))
+                .try_take(&rate, now)
+        };
+
+        match result {
+            Ok(()) => next.run(request),
+            Err(wait) => {
+                let retry_after = if wait.is_finite() {
+                    wait.ceil().max(1.0) as u64
+                } else {
+                    3600
+                };
+                Response::new(StatusCode::TOO_MANY_REQUESTS)
+                    .with_header("Retry-After", &retry_after.to_string())
+                    .with_body("Too Many Requests")
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::{Middleware, Next};
+use crate::har::{Entry, Har};
+use crate::http::{Request, Response, StatusCode};
+use crate::server::Handler;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+const DEFAULT_REDACTED: [&str; 4] = [
+    "Authorization",
+    "Proxy-Authorization",
+    "Cookie",
+    "Set-Cookie",
+];
+
+// The entries captured by a `Recorder`. Clones share them; as a handler it serves the
+// archive as JSON, e.g. on the admin listener.
+#[derive(Clone)]
+pub struct Recording {
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+}
+
+impl Recording {
+    fn new() -> Self {
+        Recording {
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn har(&self) -> Har {
+        Har::new(self.entries.lock().unwrap().iter().cloned().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl Handler for Recording {
+    fn handle_request(&self, _request: &mut Request) -> Response {
+        match serde_json::to_vec_pretty(&self.har()) {
+            Ok(json) => Response::new(StatusCode::OK)
+                .with_header("Content-Type", "application/json")
+                .with_body(json),
+            Err(e) => Response::new(StatusCode::INTERNAL_SERVER_ERROR).with_body(e.to_string()),
+        }
+    }
+}
+
+// Captures each request and its response as a HAR entry, for reproducing client reports.
+// Credentials are redacted by default; streamed response bodies are not captured.
+pub struct Recorder {
+    recording: Recording,
+    max_entries: usize,
+    max_body_size: usize,
+    redacted: Vec<String>,
+    path: Option<PathBuf>,
+    save_interval: Duration,
+    // When the archive was last written; also serializes writers so an older snapshot
+    // cannot replace a newer one.
+    last_saved: Mutex<Option<Instant>>,
+    unsaved: AtomicBool,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder {
+            recording: Recording::new(),
+            max_entries: 1000,
+            max_body_size: 64 * 1024,
+            redacted: DEFAULT_REDACTED
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            path: None,
+            save_interval: Duration::from_secs(1),
+            last_saved: Mutex::new(None),
+            unsaved: AtomicBool::new(false),
+        }
+    }
+
+    // Keeps the most recent `limit` exchanges.
+    pub fn max_entries(mut self, limit: usize) -> Self {
+        self.max_entries = limit.max(1);
+        self
+    }
+
+    // Longer request and response bodies are cut and marked as truncated.
+    pub fn max_body_size(mut self, limit: usize) -> Self {
+        self.max_body_size = limit;
+        self
+    }
+
+    // Replaces the header's value with "[REDACTED]"; a trailing `*` matches a prefix,
+    // e.g. "X-Api-*". Names are compared case-insensitively.
+    pub fn redact_header(mut self, name: &str) -> Self {
+        self.redacted.push(name.to_string());
+        self
+    }
+
+    // Writes the archive to `path` at most once per save interval, and once more when the
+    // recorder is dropped, so no exchange is lost on a clean shutdown.
+    pub fn save_to(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // How long to wait between rewrites of the saved archive; one second by default.
+    pub fn save_interval(mut self, interval: Duration) -> Self {
+        self.save_interval = interval;
+        self
+    }
+
+    pub fn recording(&self) -> Recording {
+        self.recording.clone()
+    }
+
+    fn is_redacted(&self, header: &str) -> bool {
+        self.redacted
+            .iter()
+            .any(|rule| match rule.strip_suffix('*') {
+                Some(prefix) => header
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+                None => header.eq_ignore_ascii_case(rule),
+            })
+    }
+
+    // Writes a snapshot of the entries unless one was written within the save interval or
+    // another request is writing right now; `force` waits for it and ignores the interval.
+    fn save(&self, force: bool) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut last_saved = if force {
+            self.last_saved.lock().unwrap_or_else(|e| e.into_inner())
+        } else {
+            match self.last_saved.try_lock() {
+                Ok(last_saved) => last_saved,
+                Err(_) => return,
+            }
+        };
+        if !force && last_saved.is_some_and(|at| at.elapsed() < self.save_interval) {
+            return;
+        }
+        if !self.unsaved.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        // The entries lock is only held while the snapshot is taken.
+        let har = self.recording.har();
+        if let Err(e) = har.save(path) {
+            println!("Failed to save recording to {}: {}", path.display(), e);
+        }
+        *last_saved = Some(Instant::now());
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for Recorder {
+    fn handle(&self, request: &mut Request, next: Next) -> Response {
+        let started = SystemTime::now();
+        let timer = Instant::now();
+        let response = next.run(request);
+        let entry = Entry::new(
+            request,
+            &response,
+            started,
+            timer.elapsed(),
+            self.max_body_size,
+            &|name| self.is_redacted(name),
+        );
+
+        {
+            let mut entries = self.recording.entries.lock().unwrap();
+            if entries.len() == self.max_entries {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+        if self.path.is_some() {
+            self.unsaved.store(true, Ordering::Release);
+            self.save(false);
+        }
+        response
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.save(true);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::client::{Client, ClientError, Url};
+use crate::har::{Entry, REDACTED};
+use crate::http::{Method, Response};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// Hop-by-hop and framing headers, which the client sets itself.
+const NOT_REPLAYED: [&str; 5] = [
+    "Connection",
+    "Keep-Alive",
+    "Content-Length",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+// Headers expected to change between runs; the body comparison covers Content-Length.
+const NOT_COMPARED: [&str; 5] = [
+    "Date",
+    "Connection",
+    "Keep-Alive",
+    "Content-Length",
+    "Transfer-Encoding",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Difference {
+    Status {
+        recorded: u16,
+        replayed: u16,
+    },
+    Header {
+        name: String,
+        recorded: Option<String>,
+        replayed: Option<String>,
+    },
+    Body {
+        recorded: Vec<u8>,
+        replayed: Vec<u8>,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Status { recorded, replayed } => write!(f, "status {} -> {}", recorded, replayed),
+            Self::Header {
+                name,
+                recorded,
+                replayed,
+            } => match (recorded, replayed) {
+                (Some(recorded), Some(replayed)) => {
+                    write!(f, "header {}: {:?} -> {:?}", name, recorded, replayed)
+                }
+                (Some(recorded), None) => write!(f, "header {}: {:?} missing", name, recorded),
+                (None, Some(replayed)) => write!(f, "header {}: added {:?}", name, replayed),
+                (None, None) => write!(f, "header {}", name),
+            },
+            Self::Body { recorded, replayed } => {
+                match (std::str::from_utf8(recorded), std::str::from_utf8(replayed)) {
+                    (Ok(recorded), Ok(replayed)) => {
+                        let mut recorded_lines = recorded.lines();
+                        let mut replayed_lines = replayed.lines();
+                        let mut line = 1;
+                        loop {
+                            match (recorded_lines.next(), replayed_lines.next()) {
+                                (old, new) if old == new && old.is_some() => line += 1,
+                                (old, new) => {
+                                    writeln!(f, "body differs at line {}:", line)?;
+                                    writeln!(f, "      - {}", old.unwrap_or(""))?;
+                                    return write!(f, "      + {}", new.unwrap_or(""));
+                                }
+                            }
+                        }
+                    }
+                    _ => write!(
+                        f,
+                        "body differs ({} bytes -> {} bytes)",
+                        recorded.len(),
+                        replayed.len()
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Matched,
+    Differs(Vec<Difference>),
+    // The entry cannot be replayed faithfully, e.g. its request body was truncated.
+    Skipped(String),
+    Failed(ClientError),
+}
+
+// Re-sends recorded requests to another server and compares what comes back.
+pub struct Replay {
+    base: String,
+    client: Client,
+    ignored_headers: Vec<String>,
+}
+
+impl Replay {
+    // `target` is a plain http:// URL; its path is ignored.
+    pub fn new(target: &str) -> Result<Self, ClientError> {
+        let url = Url::parse(target)?;
+        Ok(Replay {
+            base: format!("http://{}:{}", url.host, url.port),
+            client: Client::new().max_redirects(0),
+            ignored_headers: Vec::new(),
+        })
+    }
+
+    // Leaves a response header out of the comparison, e.g. a request id.
+    pub fn ignore_header(mut self, name: &str) -> Self {
+        self.ignored_headers.push(name.to_string());
+        self
+    }
+
+    pub fn run(&self, entry: &Entry) -> Outcome {
+        let recorded = &entry.request;
+        let method: Method = match recorded.method.parse() {
+            Ok(method) => method,
+            Err(_) => return Outcome::Skipped(format!("unsupported method {}", recorded.method)),
+        };
+        let body = match recorded.body() {
+            Some(body) => body,
+            None => return Outcome::Skipped(String::from("request body was not fully recorded")),
+        };
+
+        let url = format!("{}{}", self.base, request_target(&recorded.url));
+        let mut request = self.client.request(method, &url).body(body);
+        for header in &recorded.headers {
+            let skipped = NOT_REPLAYED
+                .iter()
+                .any(|name| header.name.eq_ignore_ascii_case(name));
+            if !skipped && header.value != REDACTED {
+                request = request.header(&header.name, &header.value);
+            }
+        }
+        let mut response = match request.send() {
+            Ok(response) => response,
+            Err(e) => return Outcome::Failed(e),
+        };
+        if let Err(e) = response.buffer_body() {
+            return Outcome::Failed(ClientError::Io(e));
+        }
+
+        let differences = self.compare(entry, &response);
+        if differences.is_empty() {
+            Outcome::Matched
+        } else {
+            Outcome::Differs(differences)
+        }
+    }
+
+    fn compare(&self, entry: &Entry, response: &Response) -> Vec<Difference> {
+        let recorded = &entry.response;
+        let mut differences = Vec::new();
+        if recorded.status != response.status_code().as_u16() {
+            differences.push(Difference::Status {
+                recorded: recorded.status,
+                replayed: response.status_code().as_u16(),
+            });
+        }
+
+        let compared = |name: &str| {
+            !NOT_COMPARED
+                .iter()
+                .map(|name| name.to_string())
+                .chain(self.ignored_headers.iter().cloned())
+                .any(|ignored| ignored.eq_ignore_ascii_case(name))
+        };
+        let recorded_value = |name: &str| {
+            let values: Vec<&str> = recorded
+                .headers
+                .iter()
+                .filter(|header| header.name.eq_ignore_ascii_case(name))
+                .map(|header| header.value.as_str())
+                .collect();
+            (!values.is_empty()).then(|| values.join(", "))
+        };
+        let replayed_value = |name: &str| {
+            let values: Vec<&str> = response.headers.get_all(name).collect();
+            (!values.is_empty()).then(|| values.join(", "))
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        let recorded_names = recorded.headers.iter().map(|header| header.name.as_str());
+        for name in recorded_names.chain(response.headers.iter().map(|(name, _)| name)) {
+            if compared(name) && !names.iter().any(|seen| seen.eq_ignore_ascii_case(name)) {
+                names.push(name.to_string());
+            }
+        }
+        for name in names {
+            let (recorded, replayed) = (recorded_value(&name), replayed_value(&name));
+            if recorded.as_deref() != Some(REDACTED) && recorded != replayed {
+                differences.push(Difference::Header {
+                    name,
+                    recorded,
+                    replayed,
+                });
+            }
+        }
+
+        if let (Some(recorded), Some(replayed)) = (recorded.content.body(), response.body()) {
+            if recorded != replayed {
+                differences.push(Difference::Body {
+                    recorded,
+                    replayed: replayed.to_vec(),
+                });
+            }
+        }
+        differences
+    }
+}
+
+// "https://example.com/a?b=1" -> "/a?b=1".
+fn request_target(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    match rest.find(['/', '?']) {
+        Some(i) if rest[i..].starts_with('?') => format!("/{}", &rest[i..]),
+        Some(i) => rest[i..].to_string(),
+        None => String::from("/"),
+    }
+}
@@ -0,0 +1,134 @@
+use crate::http::path::is_under;
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::middleware::{Middleware, Next};
+use crate::server::Handler;
+
+// The pattern of the route that handled a request, stored in `Request::extensions`.
+pub struct MatchedRoute(pub String);
+
+struct Route {
+    method: Option<Method>,
+    pattern: String,
+    handler: Box<dyn Handler>,
+}
+
+impl Route {
+    // "/static/*" matches "/static" and everything below it, other patterns match exactly.
+    fn matches_path(&self, path: &str) -> bool {
+        match self.pattern.strip_suffix("/*") {
+            Some(prefix) => is_under(path, prefix),
+            None => path == self.pattern,
+        }
+    }
+}
+
+struct Routes(Vec<Route>);
+
+impl Routes {
+    // The handler for the request, or the 404 or 405 response when there is none.
+    fn find(&self, request: &mut Request) -> Result<&dyn Handler, Response> {
+        let mut path_matched = false;
+        for route in self
+            .0
+            .iter()
+            .filter(|route| route.matches_path(&request.path))
+        {
+            match &route.method {
+                Some(method) if *method != request.method => path_matched = true,
+                _ => {
+                    request
+                        .extensions
+                        .insert(MatchedRoute(route.pattern.clone()));
+                    return Ok(route.handler.as_ref());
+                }
+            }
+        }
+
+        if path_matched {
+            Err(Response::new(StatusCode::METHOD_NOT_ALLOWED).with_body("Method Not Allowed"))
+        } else {
+            Err(Response::new(StatusCode::NOT_FOUND).with_body("Not Found"))
+        }
+    }
+}
+
+impl Handler for Routes {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        match self.find(request) {
+            Ok(handler) => handler.handle_request(request),
+            Err(response) => response,
+        }
+    }
+
+    fn check_head(&self, request: &mut Request) -> Option<Response> {
+        match self.find(request) {
+            Ok(handler) => handler.check_head(request),
+            Err(response) => Some(response),
+        }
+    }
+}
+
+pub struct Router {
+    routes: Routes,
+    middleware: Vec<Box<dyn Middleware>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router {
+            routes: Routes(Vec::new()),
+            middleware: Vec::new(),
+        }
+    }
+
+    pub fn route(mut self, method: Method, pattern: &str, handler: impl Handler + 'static) -> Self {
+        self.routes.0.push(Route {
+            method: Some(method),
+            pattern: pattern.to_string(),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn any(mut self, pattern: &str, handler: impl Handler + 'static) -> Self {
+        self.routes.0.push(Route {
+            method: None,
+            pattern: pattern.to_string(),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: impl Handler + 'static) -> Self {
+        self.route(Method::GET, pattern, handler)
+    }
+
+    pub fn post(self, pattern: &str, handler: impl Handler + 'static) -> Self {
+        self.route(Method::POST, pattern, handler)
+    }
+
+    // Middleware runs in the order it was added, before the matched route's handler.
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handler for Router {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        Next::new(&self.middleware, &self.routes).run(request)
+    }
+
+    fn check_head(&self, request: &mut Request) -> Option<Response> {
+        self.middleware
+            .iter()
+            .find_map(|middleware| middleware.check_head(request))
+            .or_else(|| self.routes.check_head(request))
+    }
+}
@@ -1,51 +1,733 @@
-use crate::http::Request;
+use crate::connections::ConnectionTracker;
+use crate::http::response::Upgrade;
+use crate::http::{borrowed, Method, ParseError, Request, Response, StatusCode};
+use crate::metrics::{CountingWriter, Metrics, UNMATCHED_ROUTE};
+use crate::router::MatchedRoute;
+use crate::thread_pool::ThreadPool;
+#[cfg(feature = "tls")]
+use crate::tls::{self, HttpsRedirect, TlsConfig};
+#[cfg(feature = "tls")]
+use rustls::ServerConfig;
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::io::Read;
-use std::net::TcpListener;
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub(crate) const MAX_HEAD_SIZE: usize = 8 * 1024;
+pub(crate) const CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+pub trait Handler: Send + Sync {
+    fn handle_request(&self, request: &mut Request) -> Response;
+
+    // Called with the head of a request sent with `Expect: 100-continue`, before its body
+    // is read. Returning a final response, e.g. 401 or 404, rejects the upload unread.
+    fn check_head(&self, _request: &mut Request) -> Option<Response> {
+        None
+    }
+
+    fn handle_bad_request(&self, e: &ParseError) -> Response {
+        println!("Failed to parse a request: {}", e);
+        let status = e.status_code();
+        Response::new(status).with_body(status.reason_phrase().to_string())
+    }
+}
+
+impl<F> Handler for F
+where
+    F: Fn(&mut Request) -> Response + Send + Sync,
+{
+    fn handle_request(&self, request: &mut Request) -> Response {
+        self(request)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Default)]
+pub(crate) struct ServerState {
+    pub draining: AtomicBool,
+    pub connections: ConnectionTracker,
+    pub addresses: Mutex<Vec<SocketAddr>>,
+}
+
+// Lets other threads watch a running server and ask it to drain and stop.
+#[derive(Clone)]
+pub struct ServerHandle {
+    state: Arc<ServerState>,
+}
+
+impl ServerHandle {
+    pub fn is_draining(&self) -> bool {
+        self.state.draining.load(Ordering::SeqCst)
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.state.connections.active()
+    }
+
+    // The bound listener addresses, with real ports when the server was given port 0.
+    pub fn local_addresses(&self) -> Vec<SocketAddr> {
+        self.state.addresses.lock().unwrap().clone()
+    }
+
+    // Stops accepting, closes keep-alive connections after their current response,
+    // and lets `Server::run` return once the open ones finish.
+    pub fn shutdown(&self) {
+        if self.state.draining.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // Wake the accept loops, which are blocked in accept().
+        for address in self.state.addresses.lock().unwrap().iter() {
+            let mut address = *address;
+            if address.ip().is_unspecified() {
+                address.set_ip(match address.ip() {
+                    IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                });
+            }
+            let _ = TcpStream::connect_timeout(&address, Duration::from_secs(1));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ConnectionSettings {
+    pub keep_alive_timeout: Duration,
+    pub write_timeout: Option<Duration>,
+    pub max_body_size: usize,
+    pub log_format: LogFormat,
+    pub metrics: Option<Metrics>,
+    pub state: Arc<ServerState>,
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<ServerConfig>>,
+}
 
 pub struct Server {
     pub ip_address: String,
     pub port: u32,
+    pub(crate) extra_addresses: Vec<String>,
+    pub(crate) redirect_addresses: Vec<String>,
+    pub(crate) workers: Option<usize>,
+    pub(crate) max_connections: Option<usize>,
+    pub(crate) max_connections_per_ip: Option<usize>,
+    pub(crate) shutdown_timeout: Duration,
+    pub(crate) settings: ConnectionSettings,
 }
 
 impl Server {
     pub fn new(ip_address: String, port: u32) -> Self {
-        Server { ip_address, port }
-    }
-
-    pub fn run(&mut self) {
-        println!("Server is running on {}:{}", self.ip_address, self.port);
-        let address = format!("{}:{}", self.ip_address, self.port);
-
-        let listener = TcpListener::bind(address).unwrap();
-
-        loop {
-            match listener.accept() {
-                Ok((mut stream, addr)) => {
-                    println!("Connection received from: {}", addr);
-                    let mut buffer = [0; 1024];
-                    match stream.read(&mut buffer) {
-                        Ok(_) => {
-                            println!("Received a request: {}", String::from_utf8_lossy(&buffer));
-                            match Request::try_from(&buffer[..]) {
-                                Ok(request) => {
-                                    println!("Request parsed successfully: {:?}", request);
-                                }
-                                Err(e) => {
-                                    println!("Failed to parse a request: {}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            println!("Failed to read from connection: {}", e);
+        Server {
+            ip_address,
+            port,
+            extra_addresses: Vec::new(),
+            redirect_addresses: Vec::new(),
+            workers: None,
+            max_connections: None,
+            max_connections_per_ip: None,
+            shutdown_timeout: Duration::from_secs(30),
+            settings: ConnectionSettings {
+                keep_alive_timeout: Duration::from_secs(5),
+                write_timeout: None,
+                max_body_size: 10 * 1024 * 1024,
+                log_format: LogFormat::Text,
+                metrics: None,
+                state: Arc::new(ServerState::default()),
+                #[cfg(feature = "tls")]
+                tls: None,
+            },
+        }
+    }
+
+    // Accepts connections on another address as well, e.g. "[::1]:8080".
+    pub fn listen(mut self, address: &str) -> Self {
+        self.extra_addresses.push(address.to_string());
+        self
+    }
+
+    // Serves connections from a fixed pool instead of a thread per connection.
+    pub fn workers(mut self, count: usize) -> Self {
+        self.workers = Some(count.max(1));
+        self
+    }
+
+    pub fn max_connections(mut self, limit: usize) -> Self {
+        self.max_connections = Some(limit);
+        self
+    }
+
+    pub fn max_connections_per_ip(mut self, limit: usize) -> Self {
+        self.max_connections_per_ip = Some(limit);
+        self
+    }
+
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.keep_alive_timeout = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.write_timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_body_size(mut self, limit: usize) -> Self {
+        self.settings.max_body_size = limit;
+        self
+    }
+
+    pub fn log_format(mut self, format: LogFormat) -> Self {
+        self.settings.log_format = format;
+        self
+    }
+
+    // How long `run` waits for open connections once a shutdown starts.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    // Serves HTTPS on every address given to `new` and `listen`.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.settings.tls = Some(config.server_config());
+        self
+    }
+
+    // Accepts plain HTTP on `address` and redirects each request to HTTPS on `port`.
+    #[cfg(feature = "tls")]
+    pub fn redirect_http(mut self, address: &str) -> Self {
+        self.redirect_addresses.push(address.to_string());
+        self
+    }
+
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            state: Arc::clone(&self.settings.state),
+        }
+    }
+
+    // Records request, connection and parse error counts into `metrics`.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.settings.metrics = Some(metrics);
+        self
+    }
+
+    pub fn run(&mut self, handler: impl Handler + 'static) -> IoResult<()> {
+        let mut addresses = vec![format!("{}:{}", self.ip_address, self.port)];
+        addresses.extend(self.extra_addresses.iter().cloned());
+
+        let state = Arc::clone(&self.settings.state);
+        let mut listeners = Vec::new();
+        for address in &addresses {
+            let listener = TcpListener::bind(address)?;
+            state.addresses.lock().unwrap().push(listener.local_addr()?);
+            listeners.push(listener);
+            println!("Server is running on {}", address);
+        }
+        let mut redirect_listeners = Vec::new();
+        for address in &self.redirect_addresses {
+            let listener = TcpListener::bind(address)?;
+            state.addresses.lock().unwrap().push(listener.local_addr()?);
+            redirect_listeners.push(listener);
+            println!("Redirecting HTTP on {} to HTTPS", address);
+        }
+
+        if state.draining.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let handler: Arc<dyn Handler> = Arc::new(handler);
+        let connections = state
+            .connections
+            .with_limits(self.max_connections, self.max_connections_per_ip);
+        let pool = self.workers.map(|size| Arc::new(ThreadPool::new(size)));
+
+        let mut accept_threads = Vec::new();
+        for listener in listeners {
+            let handler = Arc::clone(&handler);
+            let connections = connections.clone();
+            let pool = pool.clone();
+            let settings = self.settings.clone();
+            accept_threads.push(thread::spawn(move || {
+                accept_loop(listener, handler, connections, pool, settings)
+            }));
+        }
+        #[cfg(feature = "tls")]
+        if !redirect_listeners.is_empty() {
+            let port = state.addresses.lock().unwrap()[0].port() as u32;
+            let redirect: Arc<dyn Handler> = Arc::new(HttpsRedirect::new(port));
+            for listener in redirect_listeners {
+                let handler = Arc::clone(&redirect);
+                let connections = connections.clone();
+                let pool = pool.clone();
+                let mut settings = self.settings.clone();
+                settings.tls = None;
+                accept_threads.push(thread::spawn(move || {
+                    accept_loop(listener, handler, connections, pool, settings)
+                }));
+            }
+        }
+        for accept_thread in accept_threads {
+            let _ = accept_thread.join();
+        }
+
+        println!("Draining {} connection(s)", connections.active());
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while connections.active() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+        if connections.active() > 0 {
+            println!(
+                "Shutdown timeout reached with {} connection(s) still open",
+                connections.active()
+            );
+            // Dropping the pool would wait on those connections.
+            std::mem::forget(pool);
+        }
+        Ok(())
+    }
+}
+
+fn accept_loop(
+    listener: TcpListener,
+    handler: Arc<dyn Handler>,
+    connections: ConnectionTracker,
+    pool: Option<Arc<ThreadPool>>,
+    settings: ConnectionSettings,
+) {
+    loop {
+        let accepted = listener.accept();
+        if settings.state.draining.load(Ordering::SeqCst) {
+            break;
+        }
+        match accepted {
+            Ok((mut stream, addr)) => {
+                let guard = match connections.try_acquire(addr.ip()) {
+                    Some(guard) => guard,
+                    None => {
+                        println!("Connection limit reached, rejecting {}", addr);
+                        let response = Response::new(StatusCode::SERVICE_UNAVAILABLE)
+                            .with_header("Connection", "close")
+                            .with_header("Retry-After", "1")
+                            .with_body("Too many connections");
+                        if let Err(e) = response.send(&mut stream) {
+                            println!("Failed to send response: {}", e);
                         }
+                        continue;
+                    }
+                };
+
+                let connection_metric = settings
+                    .metrics
+                    .as_ref()
+                    .map(|metrics| metrics.connection_opened());
+                let handler = Arc::clone(&handler);
+                let settings = settings.clone();
+                let job = move || {
+                    let _guard = guard;
+                    let _connection_metric = connection_metric;
+                    handle_connection(stream, addr, handler.as_ref(), &settings);
+                };
+                match &pool {
+                    Some(pool) => pool.execute(job),
+                    None => {
+                        thread::spawn(job);
+                    }
+                }
+            }
+            Err(e) => {
+                println!("Failed to establish a connection: {}", e);
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    handler: &dyn Handler,
+    settings: &ConnectionSettings,
+) {
+    let configured = stream
+        .set_read_timeout(Some(settings.keep_alive_timeout))
+        .and_then(|_| stream.set_write_timeout(settings.write_timeout));
+    if let Err(e) = configured {
+        println!("Failed to configure connection: {}", e);
+        return;
+    }
+
+    #[cfg(feature = "tls")]
+    if let Some(config) = &settings.tls {
+        let mut reader = match tls::accept(config, stream) {
+            Ok(stream) => BufReader::new(stream),
+            Err(e) => {
+                println!("Failed to start TLS: {}", e);
+                return;
+            }
+        };
+        if serve_connection(&mut reader, addr, handler, settings).is_some() {
+            println!("Upgrades are not supported over TLS, closing {}", addr);
+        }
+        let _ = tls::close(reader.get_mut());
+        return;
+    }
+
+    let mut reader = BufReader::new(stream);
+    if let Some(upgrade) = serve_connection(&mut reader, addr, handler, settings) {
+        match reader.get_ref().try_clone() {
+            Ok(stream) => upgrade.run(reader, stream),
+            Err(e) => println!("Failed to hand over upgraded connection: {}", e),
+        }
+    }
+}
+
+// Serves requests until the connection closes; returns the protocol it was upgraded to.
+fn serve_connection<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    addr: SocketAddr,
+    handler: &dyn Handler,
+    settings: &ConnectionSettings,
+) -> Option<Upgrade> {
+    loop {
+        let read = read_request(reader, settings.max_body_size, |head| {
+            check_expectation(head, addr, |request| handler.check_head(request))
+        });
+        let (response, exchange) = match read {
+            Ok(Some(buffer)) => {
+                let started = Instant::now();
+                match parse_request(&buffer, addr, settings) {
+                    Ok(mut request) => {
+                        let response = handler.handle_request(&mut request);
+                        Exchange::new(Some(&request), response, addr, settings, started)
                     }
+                    Err(e) => Exchange::new(
+                        None,
+                        handler.handle_bad_request(&e),
+                        addr,
+                        settings,
+                        started,
+                    ),
                 }
-                Err(e) => {
-                    println!("Failed to establish a connection: {}", e);
+            }
+            Ok(None) => return None,
+            Err(ReadError::Io(e)) => {
+                if !matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) {
+                    println!("Failed to read from connection: {}", e);
                 }
+                return None;
+            }
+            Err(ReadError::Rejected(rejected)) => {
+                let (request, response) = *rejected;
+                let response = response.with_header("Connection", "close");
+                Exchange::new(Some(&request), response, addr, settings, Instant::now())
+            }
+            Err(e) => {
+                let response = e.into_response().with_header("Connection", "close");
+                if let Err(e) = response.send(reader.get_mut()) {
+                    println!("Failed to send response: {}", e);
+                }
+                return None;
+            }
+        };
+
+        let mut writer = CountingWriter::new(reader.get_mut());
+        let result = if exchange.is_head {
+            response.send_head(&mut writer)
+        } else {
+            response.send(&mut writer)
+        }
+        .and_then(|_| writer.flush());
+        let written = writer.written;
+        let (keep_alive, upgrade) = exchange.finish(settings, written);
+        if let Err(e) = result {
+            println!("Failed to send response: {}", e);
+            return None;
+        }
+        if upgrade.is_some() || !keep_alive {
+            return upgrade;
+        }
+    }
+}
+
+pub(crate) fn parse_request(
+    buffer: &[u8],
+    addr: SocketAddr,
+    settings: &ConnectionSettings,
+) -> Result<Request, ParseError> {
+    if let Some(metrics) = &settings.metrics {
+        metrics.add_bytes_received(buffer.len() as u64);
+    }
+    match Request::try_from(buffer) {
+        Ok(mut request) => {
+            request.remote_addr = Some(addr);
+            Ok(request)
+        }
+        Err(e) => {
+            if let Some(metrics) = &settings.metrics {
+                metrics.record_parse_error(&e);
+            }
+            Err(e)
+        }
+    }
+}
+
+// A handled request on its way back to the client, shared by the blocking and async servers.
+pub(crate) struct Exchange {
+    pub is_head: bool,
+    keep_alive: bool,
+    upgrade: Option<Upgrade>,
+    status: StatusCode,
+    handled: Option<(Method, String)>,
+    started: Instant,
+}
+
+impl Exchange {
+    // `request` is None when it could not be parsed. Returns the response to send.
+    pub fn new(
+        request: Option<&Request>,
+        response: Response,
+        addr: SocketAddr,
+        settings: &ConnectionSettings,
+        started: Instant,
+    ) -> (Response, Self) {
+        let mut handled = None;
+        let mut keep_alive = false;
+        let mut is_head = false;
+        if let Some(request) = request {
+            log_request(settings.log_format, addr, request, &response, started);
+            let route = match request.extensions.get::<MatchedRoute>() {
+                Some(MatchedRoute(pattern)) => pattern.clone(),
+                None => UNMATCHED_ROUTE.to_string(),
+            };
+            keep_alive = request.wants_keep_alive();
+            is_head = request.method == Method::HEAD;
+            handled = Some((request.method.clone(), route));
+        }
+
+        let keep_alive = keep_alive
+            && !settings.state.draining.load(Ordering::SeqCst)
+            && !response
+                .headers
+                .get("Connection")
+                .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        // A 101 keeps its `Connection: Upgrade`; the connection is handed over either way.
+        let mut response =
+            if keep_alive || response.status_code() == StatusCode::SWITCHING_PROTOCOLS {
+                response
+            } else {
+                response.with_header("Connection", "close")
+            };
+        let upgrade = response
+            .take_upgrade()
+            .filter(|_| response.status_code() == StatusCode::SWITCHING_PROTOCOLS);
+
+        let exchange = Exchange {
+            status: response.status_code(),
+            is_head,
+            keep_alive,
+            upgrade,
+            handled,
+            started,
+        };
+        (response, exchange)
+    }
+
+    // Records metrics once the response is written; returns whether to keep the
+    // connection open and the protocol to hand it over to, if any.
+    pub fn finish(self, settings: &ConnectionSettings, written: u64) -> (bool, Option<Upgrade>) {
+        if let Some(metrics) = &settings.metrics {
+            metrics.add_bytes_sent(written);
+            if let Some((method, route)) = &self.handled {
+                metrics.record_request(method, route, self.status, self.started.elapsed());
             }
         }
+        (self.keep_alive, self.upgrade)
     }
 }
+
+fn log_request(
+    format: LogFormat,
+    addr: SocketAddr,
+    request: &Request,
+    response: &Response,
+    started: Instant,
+) {
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+    match format {
+        LogFormat::Text => println!(
+            "{} {} {} -> {} ({:.1}ms)",
+            addr,
+            request.method,
+            request.path,
+            response.status_code(),
+            elapsed_ms
+        ),
+        LogFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "remote_addr": addr.to_string(),
+                "method": request.method.to_string(),
+                "path": request.path,
+                "query": request.query_string,
+                "status": response.status_code().as_u16(),
+                "duration_ms": elapsed_ms,
+            })
+        ),
+    }
+}
+
+pub(crate) enum ReadError {
+    Io(IoError),
+    HeadTooLarge,
+    BodyTooLarge,
+    // An `Expect` other than 100-continue.
+    ExpectationFailed,
+    // Turned away by `Handler::check_head` before the body was read.
+    Rejected(Box<(Request, Response)>),
+}
+
+impl ReadError {
+    pub fn into_response(self) -> Response {
+        match self {
+            ReadError::Io(_) => Response::new(StatusCode::BAD_REQUEST).with_body("Bad Request"),
+            ReadError::HeadTooLarge => Response::new(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+                .with_body("Request Header Fields Too Large"),
+            ReadError::BodyTooLarge => {
+                Response::new(StatusCode::PAYLOAD_TOO_LARGE).with_body("Payload Too Large")
+            }
+            ReadError::ExpectationFailed => {
+                Response::new(StatusCode::EXPECTATION_FAILED).with_body("Expectation Failed")
+            }
+            ReadError::Rejected(rejected) => rejected.1,
+        }
+    }
+}
+
+impl From<IoError> for ReadError {
+    fn from(e: IoError) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+// Reads one request head plus its Content-Length body, or None once the client hangs up.
+// `check_head` runs before the body is read and says whether to send 100 Continue.
+fn read_request<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    max_body_size: usize,
+    check_head: impl FnOnce(&[u8]) -> Result<bool, ReadError>,
+) -> Result<Option<Vec<u8>>, ReadError> {
+    let mut buffer = Vec::new();
+    loop {
+        let read = reader
+            .by_ref()
+            .take(line_limit(&buffer))
+            .read_until(b'\n', &mut buffer)?;
+        match on_head_line(&mut buffer, read)? {
+            HeadLine::Incomplete => {}
+            HeadLine::Complete => break,
+            HeadLine::Closed => return Ok(None),
+        }
+    }
+
+    let content_length = body_length(&buffer, max_body_size)?;
+    if check_head(&buffer)? && content_length > 0 {
+        let stream = reader.get_mut();
+        stream.write_all(CONTINUE)?;
+        stream.flush()?;
+    }
+    if content_length > 0 {
+        let head_length = buffer.len();
+        buffer.resize(head_length + content_length, 0);
+        reader.read_exact(&mut buffer[head_length..])?;
+    }
+    Ok(Some(buffer))
+}
+
+// The framing rules both servers read a request head by, one line at a time.
+pub(crate) enum HeadLine {
+    Incomplete,
+    Complete,
+    // The connection closed cleanly between requests.
+    Closed,
+}
+
+// How much the next line may read, one byte past the limit so an oversized head shows.
+pub(crate) fn line_limit(buffer: &[u8]) -> u64 {
+    (MAX_HEAD_SIZE + 1).saturating_sub(buffer.len()) as u64
+}
+
+// Called after each line is appended to `buffer`, with the number of bytes read. Blank
+// lines before a request are dropped.
+pub(crate) fn on_head_line(buffer: &mut Vec<u8>, read: usize) -> Result<HeadLine, ReadError> {
+    if read == 0 {
+        return if buffer.is_empty() {
+            Ok(HeadLine::Closed)
+        } else {
+            Err(IoError::new(ErrorKind::UnexpectedEof, "incomplete request").into())
+        };
+    }
+    if buffer == b"\r\n" || buffer == b"\n" {
+        buffer.clear();
+        return Ok(HeadLine::Incomplete);
+    }
+    if buffer.ends_with(b"\n\r\n") || buffer.ends_with(b"\n\n") {
+        return Ok(HeadLine::Complete);
+    }
+    if buffer.len() > MAX_HEAD_SIZE {
+        return Err(ReadError::HeadTooLarge);
+    }
+    Ok(HeadLine::Incomplete)
+}
+
+// The length of the body that follows `head`, within the limit.
+pub(crate) fn body_length(head: &[u8], max_body_size: usize) -> Result<usize, ReadError> {
+    let length = content_length(head);
+    if length > max_body_size {
+        return Err(ReadError::BodyTooLarge);
+    }
+    Ok(length)
+}
+
+// Ok(true) when the client waits for 100 Continue before sending the body. Heads that do
+// not parse are left for `parse_request` to report once the body is in.
+pub(crate) fn check_expectation(
+    head: &[u8],
+    addr: SocketAddr,
+    check_head: impl FnOnce(&mut Request) -> Option<Response>,
+) -> Result<bool, ReadError> {
+    let request = match borrowed::Request::parse_head(head) {
+        Ok(request) => request,
+        Err(_) => return Ok(false),
+    };
+    match request.header("Expect") {
+        None => return Ok(false),
+        Some(expect) if expect.eq_ignore_ascii_case("100-continue") => {}
+        Some(_) => return Err(ReadError::ExpectationFailed),
+    }
+
+    let mut request = request.into_owned();
+    request.remote_addr = Some(addr);
+    match check_head(&mut request) {
+        Some(response) => Err(ReadError::Rejected(Box::new((request, response)))),
+        None => Ok(true),
+    }
+}
+
+// The body length the parser will use. Heads it rejects, including ambiguous framing, get no
+// body: `parse_request` reports them and the connection is closed without reading further.
+fn content_length(head: &[u8]) -> usize {
+    borrowed::Request::parse_head(head)
+        .ok()
+        .and_then(|request| request.content_length().ok().flatten())
+        .unwrap_or(0)
+}
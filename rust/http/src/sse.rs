@@ -0,0 +1,229 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::server::Handler;
+use std::collections::VecDeque;
+use std::io::{Read, Result as IoResult};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SendError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+    pub retry: Option<Duration>,
+}
+
+impl Event {
+    pub fn new(data: &str) -> Self {
+        Event {
+            data: data.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_event(mut self, event: &str) -> Self {
+        self.event = Some(event.to_string());
+        self
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    // Wire form: one `data:` line per line of data, ended by a blank line. Clients end lines
+    // at "\r\n", "\r" or "\n", so all three split data and are dropped from other fields.
+    pub fn encode(&self) -> String {
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+        let mut frame = String::new();
+        if let Some(event) = &self.event {
+            frame.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            frame.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(retry) = self.retry {
+            frame.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        for line in self
+            .data
+            .split("\r\n")
+            .flat_map(|line| line.split(['\r', '\n']))
+        {
+            frame.push_str(&format!("data: {}\n", line));
+        }
+        frame.push('\n');
+        frame
+    }
+}
+
+// Sending half of an `EventStream`; fails once the client has disconnected.
+#[derive(Clone)]
+pub struct EventSender(Sender<Event>);
+
+impl EventSender {
+    pub fn send(&self, event: Event) -> Result<(), SendError<Event>> {
+        self.0.send(event)
+    }
+}
+
+// A `text/event-stream` response that stays open until the sender is dropped or the client leaves.
+pub struct EventStream {
+    receiver: Receiver<Event>,
+    replay: Vec<Event>,
+    heartbeat: Duration,
+}
+
+impl EventStream {
+    pub fn channel() -> (EventSender, EventStream) {
+        let (sender, receiver) = mpsc::channel();
+        let stream = EventStream {
+            receiver,
+            replay: Vec::new(),
+            heartbeat: DEFAULT_HEARTBEAT,
+        };
+        (EventSender(sender), stream)
+    }
+
+    // Comment lines sent while idle, which also surface a disconnected client.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = interval;
+        self
+    }
+
+    pub fn into_response(self) -> Response {
+        let mut pending = Vec::new();
+        for event in &self.replay {
+            pending.extend_from_slice(event.encode().as_bytes());
+        }
+        let reader = EventReader {
+            receiver: self.receiver,
+            heartbeat: self.heartbeat,
+            pending,
+            position: 0,
+        };
+        Response::new(StatusCode::OK)
+            .with_header("Content-Type", "text/event-stream")
+            .with_header("Cache-Control", "no-cache")
+            .with_reader(reader, None)
+    }
+}
+
+struct EventReader {
+    receiver: Receiver<Event>,
+    heartbeat: Duration,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl Read for EventReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.position == self.pending.len() {
+            let frame = match self.receiver.recv_timeout(self.heartbeat) {
+                Ok(event) => event.encode(),
+                Err(RecvTimeoutError::Timeout) => String::from(": heartbeat\n\n"),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            };
+            self.pending = frame.into_bytes();
+            self.position = 0;
+        }
+        let count = buf.len().min(self.pending.len() - self.position);
+        buf[..count].copy_from_slice(&self.pending[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+struct Subscribers {
+    senders: Vec<Sender<Event>>,
+    replay: VecDeque<Event>,
+    next_id: u64,
+}
+
+// Fans published events out to every connected client and keeps the most recent ones so a
+// reconnecting client can resume from its `Last-Event-ID`.
+#[derive(Clone)]
+pub struct EventBroadcaster {
+    subscribers: Arc<Mutex<Subscribers>>,
+    replay_capacity: usize,
+    heartbeat: Duration,
+}
+
+impl EventBroadcaster {
+    pub fn new(replay_capacity: usize) -> Self {
+        EventBroadcaster {
+            subscribers: Arc::new(Mutex::new(Subscribers {
+                senders: Vec::new(),
+                replay: VecDeque::new(),
+                next_id: 1,
+            })),
+            replay_capacity,
+            heartbeat: DEFAULT_HEARTBEAT,
+        }
+    }
+
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = interval;
+        self
+    }
+
+    // Events without an id are numbered so clients can resume after them.
+    pub fn publish(&self, mut event: Event) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if event.id.is_none() {
+            event.id = Some(subscribers.next_id.to_string());
+            subscribers.next_id += 1;
+        }
+        subscribers
+            .senders
+            .retain(|sender| sender.send(event.clone()).is_ok());
+        if self.replay_capacity > 0 {
+            if subscribers.replay.len() == self.replay_capacity {
+                subscribers.replay.pop_front();
+            }
+            subscribers.replay.push_back(event);
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().senders.len()
+    }
+
+    pub fn subscribe(&self, last_event_id: Option<&str>) -> EventStream {
+        let (sender, receiver) = mpsc::channel();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // An id that has already left the buffer cannot be resumed from, so nothing is replayed.
+        let replay = match last_event_id {
+            Some(last_id) => match subscribers
+                .replay
+                .iter()
+                .position(|event| event.id.as_deref() == Some(last_id))
+            {
+                Some(i) => subscribers.replay.iter().skip(i + 1).cloned().collect(),
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        subscribers.senders.push(sender);
+        EventStream {
+            receiver,
+            replay,
+            heartbeat: self.heartbeat,
+        }
+    }
+}
+
+impl Handler for EventBroadcaster {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        let last_event_id = request.headers.get("Last-Event-ID");
+        self.subscribe(last_event_id).into_response()
+    }
+}
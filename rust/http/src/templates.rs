@@ -0,0 +1,539 @@
+use crate::http::{Response, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::io::Error as IoError;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Where in a template something went wrong; lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    InvalidName(String),
+    Read(PathBuf, IoError),
+    Syntax(Location, String),
+    Render(Location, String),
+    Context(serde_json::Error),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid template name {:?}", name),
+            Self::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            Self::Syntax(location, message) => write!(f, "{}: {}", location, message),
+            Self::Render(location, message) => write!(f, "{}: {}", location, message),
+            Self::Context(e) => write!(f, "cannot serialize the context: {}", e),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+// HTML templates loaded from a directory:
+//
+//   {{ user.name }}            escaped output; `{{ html | raw }}` is not escaped
+//   {% if admin %}..{% else %}..{% endif %}, also `{% if not admin %}`
+//   {% for user in users %}..{{ loop.index }}..{% endfor %}
+//   {% include "header.html" %}
+//   {# a comment #}
+//
+// A newline straight after a `{% %}` tag is dropped, so tags on their own lines leave no
+// blank lines behind. Templates are parsed once and cached; with `reload`, on by default
+// in debug builds, a template whose file changed is parsed again.
+pub struct Templates {
+    dir: PathBuf,
+    reload: bool,
+    cache: Mutex<HashMap<String, Cached>>,
+}
+
+struct Cached {
+    template: Arc<Template>,
+    modified: Option<SystemTime>,
+}
+
+impl Templates {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Templates {
+            dir: dir.into(),
+            reload: cfg!(debug_assertions),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn reload(mut self, enabled: bool) -> Self {
+        self.reload = enabled;
+        self
+    }
+
+    pub fn render(&self, name: &str, context: &impl Serialize) -> Result<String, TemplateError> {
+        let context = serde_json::to_value(context).map_err(TemplateError::Context)?;
+        let mut scope = Scope {
+            root: &context,
+            locals: Vec::new(),
+        };
+        let mut output = String::new();
+        self.render_into(name, &mut scope, 0, &mut output)?;
+        Ok(output)
+    }
+
+    // Renders an HTML response; failures are logged and answered with 500.
+    pub fn response(&self, status: StatusCode, name: &str, context: &impl Serialize) -> Response {
+        match self.render(name, context) {
+            Ok(html) => Response::new(status)
+                .with_header("Content-Type", "text/html; charset=utf-8")
+                .with_body(html),
+            Err(e) => {
+                println!("Failed to render template {}: {}", name, e);
+                Response::new(StatusCode::INTERNAL_SERVER_ERROR).with_body("Internal Server Error")
+            }
+        }
+    }
+
+    fn render_into(
+        &self,
+        name: &str,
+        scope: &mut Scope,
+        depth: usize,
+        output: &mut String,
+    ) -> Result<(), TemplateError> {
+        let template = self.get(name)?;
+        template.render_nodes(&template.nodes, self, scope, depth, output)
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<Template>, TemplateError> {
+        let path = self.resolve(name)?;
+        let modified = || {
+            fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        };
+        if let Some(cached) = self.cache.lock().unwrap().get(name) {
+            if !self.reload || cached.modified == modified() {
+                return Ok(cached.template.clone());
+            }
+        }
+
+        let modified = modified();
+        let source = fs::read_to_string(&path).map_err(|e| TemplateError::Read(path, e))?;
+        let template = Arc::new(Template::parse(name, source)?);
+        self.cache.lock().unwrap().insert(
+            name.to_string(),
+            Cached {
+                template: template.clone(),
+                modified,
+            },
+        );
+        Ok(template)
+    }
+
+    // Names are relative to the directory and may not leave it.
+    fn resolve(&self, name: &str) -> Result<PathBuf, TemplateError> {
+        let relative = Path::new(name);
+        let inside = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if name.is_empty() || !inside {
+            return Err(TemplateError::InvalidName(name.to_string()));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+struct Template {
+    name: String,
+    source: String,
+    nodes: Vec<Node>,
+}
+
+// A dotted name such as `user.name` or `items.0`.
+type Variable = Vec<String>;
+
+enum Node {
+    Text(Range<usize>),
+    Output {
+        variable: Variable,
+        raw: bool,
+        at: usize,
+    },
+    If {
+        condition: Variable,
+        negated: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        name: String,
+        items: Variable,
+        body: Vec<Node>,
+        at: usize,
+    },
+    Include {
+        name: String,
+        at: usize,
+    },
+}
+
+enum Token<'a> {
+    Text(Range<usize>),
+    Output(&'a str, usize),
+    Tag(&'a str, usize),
+}
+
+impl Template {
+    fn parse(name: &str, source: String) -> Result<Self, TemplateError> {
+        let mut template = Template {
+            name: name.to_string(),
+            source,
+            nodes: Vec::new(),
+        };
+        let tokens = template.tokenize()?;
+        let mut tokens = tokens.into_iter();
+        let (nodes, end) = template.parse_block(&mut tokens, &[])?;
+        debug_assert!(end.is_none());
+        template.nodes = nodes;
+        Ok(template)
+    }
+
+    fn tokenize(&self) -> Result<Vec<Token<'_>>, TemplateError> {
+        let source = self.source.as_str();
+        let mut tokens = Vec::new();
+        let (mut text_start, mut i) = (0, 0);
+        while let Some(found) = source[i..].find('{') {
+            let at = i + found;
+            let (kind, close) = match source.as_bytes().get(at + 1) {
+                Some(b'{') => ('{', "}}"),
+                Some(b'%') => ('%', "%}"),
+                Some(b'#') => ('#', "#}"),
+                _ => {
+                    i = at + 1;
+                    continue;
+                }
+            };
+            if text_start < at {
+                tokens.push(Token::Text(text_start..at));
+            }
+            let inner_start = at + 2;
+            let end = match source[inner_start..].find(close) {
+                Some(length) => inner_start + length,
+                None => return Err(self.syntax_error(at, format!("unclosed {{{}", kind))),
+            };
+            let inner = source[inner_start..end].trim();
+            match kind {
+                '{' => tokens.push(Token::Output(inner, at)),
+                '%' => tokens.push(Token::Tag(inner, at)),
+                _ => {}
+            }
+            i = end + 2;
+            if kind == '%' {
+                if source[i..].starts_with("\r\n") {
+                    i += 2;
+                } else if source[i..].starts_with('\n') {
+                    i += 1;
+                }
+            }
+            text_start = i;
+        }
+        if text_start < source.len() {
+            tokens.push(Token::Text(text_start..source.len()));
+        }
+        Ok(tokens)
+    }
+
+    // Parses nodes up to one of the `ends` tags and returns which one closed the block.
+    fn parse_block<'a>(
+        &self,
+        tokens: &mut impl Iterator<Item = Token<'a>>,
+        ends: &[&str],
+    ) -> Result<(Vec<Node>, Option<&'a str>), TemplateError> {
+        let mut nodes = Vec::new();
+        while let Some(token) = tokens.next() {
+            let (tag, at) = match token {
+                Token::Text(range) => {
+                    nodes.push(Node::Text(range));
+                    continue;
+                }
+                Token::Output(inner, at) => {
+                    let (expression, raw) = match inner.split_once('|') {
+                        Some((expression, filter)) if filter.trim() == "raw" => (expression, true),
+                        Some((_, filter)) => {
+                            let message = format!("unknown filter {:?}", filter.trim());
+                            return Err(self.syntax_error(at, message));
+                        }
+                        None => (inner, false),
+                    };
+                    nodes.push(Node::Output {
+                        variable: self.variable(expression.trim(), at)?,
+                        raw,
+                        at,
+                    });
+                    continue;
+                }
+                Token::Tag(tag, at) => (tag, at),
+            };
+
+            let (keyword, rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            let rest = rest.trim();
+            if ends.contains(&keyword) && rest.is_empty() {
+                return Ok((nodes, Some(keyword)));
+            }
+            match keyword {
+                "if" => {
+                    let (negated, condition) = match rest.strip_prefix("not ") {
+                        Some(condition) => (true, condition.trim()),
+                        None => (false, rest),
+                    };
+                    let condition = self.variable(condition, at)?;
+                    let (then, end) = self.parse_block(tokens, &["else", "endif"])?;
+                    let otherwise = match end {
+                        Some("else") => match self.parse_block(tokens, &["endif"])? {
+                            (otherwise, Some(_)) => otherwise,
+                            (_, None) => return Err(self.syntax_error(at, "unclosed if")),
+                        },
+                        Some(_) => Vec::new(),
+                        None => return Err(self.syntax_error(at, "unclosed if")),
+                    };
+                    nodes.push(Node::If {
+                        condition,
+                        negated,
+                        then,
+                        otherwise,
+                    });
+                }
+                "for" => {
+                    let (name, items) = match rest.split_once(" in ") {
+                        Some((name, items)) => (name.trim(), items.trim()),
+                        None => return Err(self.syntax_error(at, "expected `for NAME in ITEMS`")),
+                    };
+                    if !is_identifier(name) {
+                        let message = format!("invalid loop variable {:?}", name);
+                        return Err(self.syntax_error(at, message));
+                    }
+                    let items = self.variable(items, at)?;
+                    let body = match self.parse_block(tokens, &["endfor"])? {
+                        (body, Some(_)) => body,
+                        (_, None) => return Err(self.syntax_error(at, "unclosed for")),
+                    };
+                    nodes.push(Node::For {
+                        name: name.to_string(),
+                        items,
+                        body,
+                        at,
+                    });
+                }
+                "include" => {
+                    let name = rest
+                        .strip_prefix('"')
+                        .and_then(|rest| rest.strip_suffix('"'))
+                        .filter(|name| !name.is_empty())
+                        .ok_or_else(|| self.syntax_error(at, "expected `include \"NAME\"`"))?;
+                    nodes.push(Node::Include {
+                        name: name.to_string(),
+                        at,
+                    });
+                }
+                "else" | "endif" | "endfor" => {
+                    return Err(self.syntax_error(at, format!("unexpected {}", tag)));
+                }
+                _ => return Err(self.syntax_error(at, format!("unknown tag {:?}", keyword))),
+            }
+        }
+        Ok((nodes, None))
+    }
+
+    fn variable(&self, expression: &str, at: usize) -> Result<Variable, TemplateError> {
+        let segments: Variable = expression.split('.').map(str::to_string).collect();
+        if segments.iter().all(|segment| is_identifier(segment)) {
+            Ok(segments)
+        } else {
+            let message = format!("invalid variable {:?}", expression);
+            Err(self.syntax_error(at, message))
+        }
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        templates: &Templates,
+        scope: &mut Scope,
+        depth: usize,
+        output: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(range) => output.push_str(&self.source[range.clone()]),
+                Node::Output { variable, raw, at } => {
+                    let value = scope
+                        .lookup(variable)
+                        .ok_or_else(|| self.undefined(variable, *at))?;
+                    let text = match value {
+                        Value::Null => String::new(),
+                        Value::String(text) => text.clone(),
+                        Value::Bool(_) | Value::Number(_) => value.to_string(),
+                        Value::Array(_) | Value::Object(_) => {
+                            let message = format!("cannot display {}", variable.join("."));
+                            return Err(self.render_error(*at, message));
+                        }
+                    };
+                    if *raw {
+                        output.push_str(&text);
+                    } else {
+                        escape_into(&text, output);
+                    }
+                }
+                Node::If {
+                    condition,
+                    negated,
+                    then,
+                    otherwise,
+                } => {
+                    let truthy = scope.lookup(condition).is_some_and(is_truthy);
+                    let branch = if truthy != *negated { then } else { otherwise };
+                    self.render_nodes(branch, templates, scope, depth, output)?;
+                }
+                Node::For {
+                    name,
+                    items,
+                    body,
+                    at,
+                } => {
+                    let values = match scope.lookup(items) {
+                        Some(Value::Array(values)) => values.clone(),
+                        Some(_) => {
+                            let message = format!("cannot loop over {}", items.join("."));
+                            return Err(self.render_error(*at, message));
+                        }
+                        None => return Err(self.undefined(items, *at)),
+                    };
+                    let count = values.len();
+                    for (i, value) in values.into_iter().enumerate() {
+                        let state = json!({
+                            "index": i + 1,
+                            "first": i == 0,
+                            "last": i + 1 == count,
+                        });
+                        scope.locals.push((name.clone(), value));
+                        scope.locals.push((String::from("loop"), state));
+                        let rendered = self.render_nodes(body, templates, scope, depth, output);
+                        scope.locals.truncate(scope.locals.len() - 2);
+                        rendered?;
+                    }
+                }
+                Node::Include { name, at } => {
+                    if depth == MAX_INCLUDE_DEPTH {
+                        return Err(self.render_error(*at, "includes nested too deeply"));
+                    }
+                    templates
+                        .render_into(name, scope, depth + 1, output)
+                        .map_err(|e| match e {
+                            TemplateError::Read(..) | TemplateError::InvalidName(_) => {
+                                self.render_error(*at, e.to_string())
+                            }
+                            e => e,
+                        })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn location(&self, at: usize) -> Location {
+        let before = &self.source[..at];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            file: self.name.clone(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn syntax_error(&self, at: usize, message: impl Into<String>) -> TemplateError {
+        TemplateError::Syntax(self.location(at), message.into())
+    }
+
+    fn render_error(&self, at: usize, message: impl Into<String>) -> TemplateError {
+        TemplateError::Render(self.location(at), message.into())
+    }
+
+    fn undefined(&self, variable: &Variable, at: usize) -> TemplateError {
+        self.render_error(at, format!("undefined variable {}", variable.join(".")))
+    }
+}
+
+// The context plus the variables bound by enclosing loops, innermost last.
+struct Scope<'a> {
+    root: &'a Value,
+    locals: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    fn lookup(&self, variable: &Variable) -> Option<&Value> {
+        let (first, rest) = variable.split_first()?;
+        let mut value = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => self.root.get(first)?,
+        };
+        for segment in rest {
+            value = match value {
+                Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                _ => value.get(segment)?,
+            };
+        }
+        Some(value)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+// null, false, 0, "" and empty arrays and objects are false.
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    escape_into(text, &mut escaped);
+    escaped
+}
+
+fn escape_into(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+}
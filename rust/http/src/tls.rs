@@ -0,0 +1,164 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::server::Handler;
+use crate::virtual_hosts::{strip_port, HostTable};
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::io::{Error as IoError, Result as IoResult, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+#[derive(Debug)]
+pub enum TlsError {
+    Read(PathBuf, IoError),
+    InvalidPem(IoError),
+    NoCertificate,
+    NoPrivateKey,
+    UnsupportedKey(rustls::Error),
+}
+
+impl Display for TlsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            Self::InvalidPem(e) => write!(f, "invalid PEM: {}", e),
+            Self::NoCertificate => write!(f, "no certificate found in PEM"),
+            Self::NoPrivateKey => write!(f, "no private key found in PEM"),
+            Self::UnsupportedKey(e) => write!(f, "unsupported private key: {}", e),
+        }
+    }
+}
+
+impl Error for TlsError {}
+
+// A certificate chain and its private key.
+#[derive(Clone)]
+pub struct Certificate(Arc<CertifiedKey>);
+
+impl Certificate {
+    // The chain is read leaf first; the key may be PKCS#8, PKCS#1 or SEC1.
+    pub fn from_pem(chain: &[u8], key: &[u8]) -> Result<Self, TlsError> {
+        let chain = rustls_pemfile::certs(&mut &chain[..])
+            .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+            .map_err(TlsError::InvalidPem)?;
+        if chain.is_empty() {
+            return Err(TlsError::NoCertificate);
+        }
+        let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut &key[..])
+            .map_err(TlsError::InvalidPem)?
+            .ok_or(TlsError::NoPrivateKey)?;
+        let key = ring::sign::any_supported_type(&key).map_err(TlsError::UnsupportedKey)?;
+        Ok(Certificate(Arc::new(CertifiedKey::new(chain, key))))
+    }
+
+    pub fn from_pem_files(chain: &Path, key: &Path) -> Result<Self, TlsError> {
+        let read = |path: &Path| fs::read(path).map_err(|e| TlsError::Read(path.to_path_buf(), e));
+        Self::from_pem(&read(chain)?, &read(key)?)
+    }
+}
+
+// Certificates for `Server::tls`, picked by the name the client asks for (SNI).
+pub struct TlsConfig {
+    default: Certificate,
+    hosts: HostTable<Certificate>,
+}
+
+impl TlsConfig {
+    // Served to clients that send no server name or one without its own certificate.
+    pub fn new(default: Certificate) -> Self {
+        TlsConfig {
+            default,
+            hosts: HostTable::new(),
+        }
+    }
+
+    // Accepts "example.com" or "*.example.com", like `VirtualHosts::host`.
+    pub fn host(mut self, name: &str, certificate: Certificate) -> Self {
+        self.hosts.insert(name, certificate);
+        self
+    }
+
+    pub(crate) fn server_config(self) -> Arc<ServerConfig> {
+        let mut config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self));
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Arc::new(config)
+    }
+}
+
+impl Debug for TlsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "TlsConfig({} host certificate(s))", self.hosts.len())
+    }
+}
+
+impl ResolvesServerCert for TlsConfig {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certificate = client_hello
+            .server_name()
+            .and_then(|name| self.hosts.find(name))
+            .unwrap_or(&self.default);
+        Some(Arc::clone(&certificate.0))
+    }
+}
+
+// The handshake runs on the first read, under the connection's read timeout.
+pub(crate) fn accept(
+    config: &Arc<ServerConfig>,
+    stream: TcpStream,
+) -> Result<TlsStream, rustls::Error> {
+    let connection = ServerConnection::new(Arc::clone(config))?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+// Flushing mid-handshake would wait for the client, so failed handshakes just drop.
+pub(crate) fn close(stream: &mut TlsStream) -> IoResult<()> {
+    if stream.conn.is_handshaking() {
+        return Ok(());
+    }
+    stream.conn.send_close_notify();
+    stream.flush()
+}
+
+// Answers every request on a plain listener with a redirect to the same URL over HTTPS.
+pub struct HttpsRedirect {
+    port: u32,
+}
+
+impl HttpsRedirect {
+    // `port` is the HTTPS listener's; 443 is left out of the URL.
+    pub fn new(port: u32) -> Self {
+        HttpsRedirect { port }
+    }
+}
+
+impl Handler for HttpsRedirect {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        let host = match request.headers.get("Host") {
+            Some(host) if !host.trim().is_empty() => strip_port(host.trim()),
+            _ => return Response::new(StatusCode::BAD_REQUEST).with_body("Missing Host header"),
+        };
+        let mut location = match self.port {
+            443 => format!("https://{}{}", host, request.path),
+            port => format!("https://{}:{}{}", host, port, request.path),
+        };
+        if let Some(query_string) = &request.query_string {
+            location.push('?');
+            location.push_str(query_string);
+        }
+        Response::new(StatusCode::PERMANENT_REDIRECT)
+            .with_header("Location", &location)
+            .with_body("Permanent Redirect")
+    }
+}
@@ -0,0 +1,148 @@
+use crate::http::{Request, Response, StatusCode};
+use crate::server::Handler;
+
+enum HostPattern {
+    Exact(String),
+    // "*.example.com" is stored as ".example.com" and matches any subdomain, not the apex.
+    Wildcard(String),
+}
+
+// Values keyed by host name, shared by virtual hosts and TLS certificate selection.
+pub(crate) struct HostTable<T> {
+    entries: Vec<(HostPattern, T)>,
+}
+
+impl<T> HostTable<T> {
+    pub fn new() -> Self {
+        HostTable {
+            entries: Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Accepts "example.com" or "*.example.com"; names are compared case-insensitively.
+    pub fn insert(&mut self, name: &str, value: T) {
+        let name = normalize(name);
+        let pattern = match name.strip_prefix('*') {
+            Some(suffix) => HostPattern::Wildcard(suffix.to_string()),
+            None => HostPattern::Exact(name),
+        };
+        self.entries.push((pattern, value));
+    }
+
+    // Exact names win over wildcards, and longer wildcards over shorter ones.
+    pub fn find(&self, host: &str) -> Option<&T> {
+        let host = normalize(host);
+        let exact = self.entries.iter().find(|(pattern, _)| match pattern {
+            HostPattern::Exact(name) => *name == host,
+            HostPattern::Wildcard(_) => false,
+        });
+        let entry = exact.or_else(|| {
+            self.entries
+                .iter()
+                .filter_map(|entry| match &entry.0 {
+                    HostPattern::Wildcard(suffix)
+                        if host.len() > suffix.len() && host.ends_with(suffix.as_str()) =>
+                    {
+                        Some((suffix.len(), entry))
+                    }
+                    _ => None,
+                })
+                .max_by_key(|(length, _)| *length)
+                .map(|(_, entry)| entry)
+        });
+        entry.map(|(_, value)| value)
+    }
+}
+
+// Dispatches each request to the site registered for its Host header.
+pub struct VirtualHosts {
+    sites: HostTable<Box<dyn Handler>>,
+    fallback: Option<Box<dyn Handler>>,
+    unknown_status: StatusCode,
+}
+
+impl VirtualHosts {
+    pub fn new() -> Self {
+        VirtualHosts {
+            sites: HostTable::new(),
+            fallback: None,
+            unknown_status: StatusCode::NOT_FOUND,
+        }
+    }
+
+    // Accepts "example.com" or "*.example.com"; names are compared case-insensitively.
+    pub fn host(mut self, name: &str, handler: impl Handler + 'static) -> Self {
+        self.sites.insert(name, Box::new(handler));
+        self
+    }
+
+    // Serves requests whose Host matches no site, and requests without one.
+    pub fn fallback(mut self, handler: impl Handler + 'static) -> Self {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    // Status for unknown hosts when there is no fallback site, usually 404 or 421.
+    pub fn unknown_status(mut self, status: StatusCode) -> Self {
+        self.unknown_status = status;
+        self
+    }
+
+    fn site(&self, request: &Request) -> Result<&dyn Handler, Response> {
+        request
+            .headers
+            .get("Host")
+            .and_then(|host| self.sites.find(strip_port(host)))
+            .or(self.fallback.as_ref())
+            .map(Box::as_ref)
+            .ok_or_else(|| {
+                Response::new(self.unknown_status)
+                    .with_body(self.unknown_status.reason_phrase().to_string())
+            })
+    }
+}
+
+impl Default for VirtualHosts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handler for VirtualHosts {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        match self.site(request) {
+            Ok(handler) => handler.handle_request(request),
+            Err(response) => response,
+        }
+    }
+
+    fn check_head(&self, request: &mut Request) -> Option<Response> {
+        match self.site(request) {
+            Ok(handler) => handler.check_head(request),
+            Err(response) => Some(response),
+        }
+    }
+}
+
+fn normalize(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+// "example.com:8080" -> "example.com", "[::1]:8080" -> "[::1]".
+pub(crate) fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
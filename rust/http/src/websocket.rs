@@ -0,0 +1,481 @@
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::server::Handler;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+pub const CLOSE_NORMAL: u16 = 1000;
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const CLOSE_INVALID_DATA: u16 = 1007;
+pub const CLOSE_TOO_BIG: u16 = 1009;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close(Option<(u16, String)>),
+}
+
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(GUID.as_bytes());
+    STANDARD.encode(hasher.finalize())
+}
+
+fn has_token(value: Option<&str>, token: &str) -> bool {
+    value.is_some_and(|value| {
+        value
+            .split(',')
+            .any(|part| part.trim().eq_ignore_ascii_case(token))
+    })
+}
+
+pub fn is_upgrade_request(request: &Request) -> bool {
+    request.method == Method::GET
+        && has_token(request.headers.get("Upgrade"), "websocket")
+        && has_token(request.headers.get("Connection"), "upgrade")
+}
+
+// Write half shared between the socket and any senders handed out to other threads.
+#[derive(Clone)]
+pub struct WebSocketSender {
+    stream: Arc<Mutex<TcpStream>>,
+    max_frame_size: usize,
+}
+
+impl WebSocketSender {
+    pub fn send(&self, message: &Message) -> IoResult<()> {
+        let mut stream = self.stream.lock().unwrap();
+        match message {
+            Message::Text(text) => write_fragmented(
+                &mut *stream,
+                OPCODE_TEXT,
+                text.as_bytes(),
+                self.max_frame_size,
+            ),
+            Message::Binary(data) => {
+                write_fragmented(&mut *stream, OPCODE_BINARY, data, self.max_frame_size)
+            }
+            Message::Ping(data) => write_frame(&mut *stream, true, OPCODE_PING, data),
+            Message::Pong(data) => write_frame(&mut *stream, true, OPCODE_PONG, data),
+            Message::Close(reason) => {
+                let mut payload = Vec::new();
+                if let Some((code, text)) = reason {
+                    payload.extend_from_slice(&code.to_be_bytes());
+                    payload.extend_from_slice(text.as_bytes());
+                    payload.truncate(125);
+                }
+                write_frame(&mut *stream, true, OPCODE_CLOSE, &payload)
+            }
+        }
+    }
+
+    pub fn send_text(&self, text: &str) -> IoResult<()> {
+        self.send(&Message::Text(text.to_string()))
+    }
+}
+
+pub struct WebSocket {
+    reader: BufReader<TcpStream>,
+    sender: WebSocketSender,
+    max_message_size: usize,
+    // A fragmented message still waiting for its final frame.
+    partial: Option<(u8, Vec<u8>)>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl WebSocket {
+    fn new(reader: BufReader<TcpStream>, stream: TcpStream, options: &Options) -> Self {
+        WebSocket {
+            reader,
+            sender: WebSocketSender {
+                stream: Arc::new(Mutex::new(stream)),
+                max_frame_size: options.max_frame_size,
+            },
+            max_message_size: options.max_message_size,
+            partial: None,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    pub fn sender(&self) -> WebSocketSender {
+        self.sender.clone()
+    }
+
+    pub fn send(&mut self, message: &Message) -> IoResult<()> {
+        if self.close_sent {
+            return Err(IoError::new(ErrorKind::NotConnected, "close already sent"));
+        }
+        if let Message::Close(_) = message {
+            self.close_sent = true;
+        }
+        self.sender.send(message)
+    }
+
+    pub fn send_text(&mut self, text: &str) -> IoResult<()> {
+        self.send(&Message::Text(text.to_string()))
+    }
+
+    // Waits for the next data message. Pings are answered here and surfaced to the caller;
+    // a peer's Close is echoed back and returned, after which the socket is finished.
+    pub fn recv(&mut self) -> IoResult<Message> {
+        if self.close_received {
+            return Err(IoError::new(ErrorKind::NotConnected, "connection closed"));
+        }
+
+        loop {
+            let frame = match read_frame(&mut self.reader, self.max_message_size) {
+                Ok(frame) => frame,
+                Err(FrameError::Io(e)) => return Err(e),
+                Err(FrameError::Protocol(code, reason)) => return Err(self.fail(code, reason)),
+            };
+
+            match frame.opcode {
+                OPCODE_PING => {
+                    if !self.close_sent {
+                        self.sender.send(&Message::Pong(frame.payload.clone()))?;
+                    }
+                    return Ok(Message::Ping(frame.payload));
+                }
+                OPCODE_PONG => return Ok(Message::Pong(frame.payload)),
+                OPCODE_CLOSE => return self.on_close(frame.payload),
+                OPCODE_TEXT | OPCODE_BINARY => {
+                    if self.partial.is_some() {
+                        return Err(self.fail(CLOSE_PROTOCOL_ERROR, "expected continuation"));
+                    }
+                    self.partial = Some((frame.opcode, frame.payload));
+                }
+                OPCODE_CONTINUATION => match self.partial.as_mut() {
+                    Some((_, data)) => {
+                        if data.len() + frame.payload.len() > self.max_message_size {
+                            return Err(self.fail(CLOSE_TOO_BIG, "message too big"));
+                        }
+                        data.extend_from_slice(&frame.payload);
+                    }
+                    None => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "unexpected continuation")),
+                },
+                _ => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "unknown opcode")),
+            }
+
+            if frame.fin {
+                if let Some((opcode, data)) = self.partial.take() {
+                    return if opcode == OPCODE_TEXT {
+                        match String::from_utf8(data) {
+                            Ok(text) => Ok(Message::Text(text)),
+                            Err(_) => Err(self.fail(CLOSE_INVALID_DATA, "invalid UTF-8")),
+                        }
+                    } else {
+                        Ok(Message::Binary(data))
+                    };
+                }
+            }
+        }
+    }
+
+    // Starts the closing handshake and waits briefly for the peer to answer it.
+    pub fn close(&mut self, code: u16, reason: &str) -> IoResult<()> {
+        if !self.close_sent {
+            self.send(&Message::Close(Some((code, reason.to_string()))))?;
+        }
+        let _ = self
+            .reader
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_secs(5)));
+        while !self.close_received {
+            match self.recv() {
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        self.shutdown();
+        Ok(())
+    }
+
+    fn on_close(&mut self, payload: Vec<u8>) -> IoResult<Message> {
+        self.close_received = true;
+        let reason = match payload.len() {
+            0 => None,
+            1 => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "invalid close payload")),
+            _ => {
+                let code = u16::from_be_bytes([payload[0], payload[1]]);
+                match String::from_utf8(payload[2..].to_vec()) {
+                    Ok(text) => Some((code, text)),
+                    Err(_) => return Err(self.fail(CLOSE_INVALID_DATA, "invalid close reason")),
+                }
+            }
+        };
+        if !self.close_sent {
+            self.close_sent = true;
+            let echo = reason.as_ref().map(|(code, _)| (*code, String::new()));
+            self.sender.send(&Message::Close(echo))?;
+        }
+        self.shutdown();
+        Ok(Message::Close(reason))
+    }
+
+    // Closes the connection after a protocol violation by the peer.
+    fn fail(&mut self, code: u16, reason: &str) -> IoError {
+        if !self.close_sent {
+            self.close_sent = true;
+            let _ = self
+                .sender
+                .send(&Message::Close(Some((code, reason.to_string()))));
+        }
+        self.close_received = true;
+        self.shutdown();
+        IoError::new(ErrorKind::InvalidData, reason.to_string())
+    }
+
+    fn shutdown(&self) {
+        let _ = self.reader.get_ref().shutdown(Shutdown::Both);
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+enum FrameError {
+    Io(IoError),
+    Protocol(u16, &'static str),
+}
+
+impl From<IoError> for FrameError {
+    fn from(e: IoError) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+fn read_frame(reader: &mut impl BufRead, max_payload: usize) -> Result<Frame, FrameError> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+
+    if header[0] & 0x70 != 0 {
+        return Err(FrameError::Protocol(
+            CLOSE_PROTOCOL_ERROR,
+            "reserved bits set",
+        ));
+    }
+    // Clients must mask every frame they send.
+    if !masked {
+        return Err(FrameError::Protocol(CLOSE_PROTOCOL_ERROR, "unmasked frame"));
+    }
+
+    let length = match header[1] & 0x7F {
+        126 => {
+            let mut bytes = [0; 2];
+            reader.read_exact(&mut bytes)?;
+            u16::from_be_bytes(bytes) as u64
+        }
+        127 => {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            u64::from_be_bytes(bytes)
+        }
+        length => length as u64,
+    };
+
+    let is_control = opcode & 0x8 != 0;
+    if is_control && (!fin || length > 125) {
+        return Err(FrameError::Protocol(
+            CLOSE_PROTOCOL_ERROR,
+            "invalid control frame",
+        ));
+    }
+    if length > max_payload as u64 {
+        return Err(FrameError::Protocol(CLOSE_TOO_BIG, "message too big"));
+    }
+
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask)?;
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+fn write_frame(stream: &mut impl Write, fin: bool, opcode: u8, payload: &[u8]) -> IoResult<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(if fin { 0x80 } else { 0 } | opcode);
+    match payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+fn write_fragmented(
+    stream: &mut impl Write,
+    opcode: u8,
+    payload: &[u8],
+    max_frame_size: usize,
+) -> IoResult<()> {
+    if payload.len() <= max_frame_size {
+        return write_frame(stream, true, opcode, payload);
+    }
+    let mut chunks = payload.chunks(max_frame_size).peekable();
+    let mut opcode = opcode;
+    while let Some(chunk) = chunks.next() {
+        write_frame(stream, chunks.peek().is_none(), opcode, chunk)?;
+        opcode = OPCODE_CONTINUATION;
+    }
+    Ok(())
+}
+
+struct Options {
+    max_message_size: usize,
+    max_frame_size: usize,
+    idle_timeout: Option<Duration>,
+    protocols: Vec<String>,
+}
+
+type OnConnect = dyn Fn(WebSocket, &Request) + Send + Sync;
+
+// Completes the handshake on GET requests and runs `on_connect` on the connection's thread.
+pub struct WebSocketHandler {
+    on_connect: Arc<OnConnect>,
+    options: Arc<Options>,
+}
+
+impl WebSocketHandler {
+    pub fn new(on_connect: impl Fn(WebSocket, &Request) + Send + Sync + 'static) -> Self {
+        WebSocketHandler {
+            on_connect: Arc::new(on_connect),
+            options: Arc::new(Options {
+                max_message_size: 1024 * 1024,
+                max_frame_size: 64 * 1024,
+                idle_timeout: None,
+                protocols: Vec::new(),
+            }),
+        }
+    }
+
+    fn options_mut(&mut self) -> &mut Options {
+        Arc::get_mut(&mut self.options).expect("options are only changed while building")
+    }
+
+    pub fn max_message_size(mut self, limit: usize) -> Self {
+        self.options_mut().max_message_size = limit;
+        self
+    }
+
+    // Outgoing messages larger than this are split into continuation frames.
+    pub fn max_frame_size(mut self, limit: usize) -> Self {
+        self.options_mut().max_frame_size = limit.max(1);
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.options_mut().idle_timeout = Some(timeout);
+        self
+    }
+
+    // Subprotocols in order of preference; the first one the client offers is selected.
+    pub fn protocols(mut self, protocols: &[&str]) -> Self {
+        self.options_mut().protocols = protocols.iter().map(|p| p.to_string()).collect();
+        self
+    }
+}
+
+impl Handler for WebSocketHandler {
+    fn handle_request(&self, request: &mut Request) -> Response {
+        if !is_upgrade_request(request) {
+            return Response::new(StatusCode::UPGRADE_REQUIRED)
+                .with_header("Upgrade", "websocket")
+                .with_header("Connection", "Upgrade")
+                .with_body("WebSocket upgrade required");
+        }
+        if request.headers.get("Sec-WebSocket-Version") != Some("13") {
+            return Response::new(StatusCode::UPGRADE_REQUIRED)
+                .with_header("Sec-WebSocket-Version", "13")
+                .with_body("Unsupported WebSocket version");
+        }
+        let key = match request.headers.get("Sec-WebSocket-Key") {
+            Some(key) if STANDARD.decode(key.trim()).is_ok_and(|key| key.len() == 16) => key,
+            _ => {
+                return Response::new(StatusCode::BAD_REQUEST)
+                    .with_body("Invalid Sec-WebSocket-Key")
+            }
+        };
+
+        let offered: Vec<&str> = request
+            .headers
+            .get_all("Sec-WebSocket-Protocol")
+            .flat_map(|value| value.split(','))
+            .map(|protocol| protocol.trim())
+            .collect();
+        let protocol = self
+            .options
+            .protocols
+            .iter()
+            .find(|protocol| offered.contains(&protocol.as_str()))
+            .cloned();
+
+        let mut response = Response::new(StatusCode::SWITCHING_PROTOCOLS)
+            .with_header("Upgrade", "websocket")
+            .with_header("Connection", "Upgrade")
+            .with_header("Sec-WebSocket-Accept", &accept_key(key));
+        if let Some(protocol) = &protocol {
+            response.headers.insert("Sec-WebSocket-Protocol", protocol);
+        }
+
+        let on_connect = Arc::clone(&self.on_connect);
+        let options = Arc::clone(&self.options);
+        let handshake = Request {
+            path: request.path.clone(),
+            query_string: request.query_string.clone(),
+            method: request.method.clone(),
+            headers: request.headers.clone(),
+            body: Vec::new(),
+            remote_addr: request.remote_addr,
+            extensions: Default::default(),
+        };
+        response.with_upgrade(move |reader, stream| {
+            if let Err(e) = stream.set_read_timeout(options.idle_timeout) {
+                println!("Failed to configure WebSocket: {}", e);
+                return;
+            }
+            on_connect(WebSocket::new(reader, stream, &options), &handshake);
+        })
+    }
+}
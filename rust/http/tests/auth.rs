@@ -0,0 +1,65 @@
+use http::http::{Request, Response, StatusCode};
+use http::middleware::{Auth, Credentials, Principal, Scheme};
+use http::router::Router;
+use http::server::Handler;
+
+fn router() -> Router {
+    let checker = |credentials: &Credentials| match credentials {
+        Credentials::Basic { username, password } if username == "ada" && password == "pw" => {
+            Some(Principal {
+                name: username.clone(),
+                scheme: Scheme::Basic,
+            })
+        }
+        _ => None,
+    };
+    Router::new()
+        .with(Auth::new("test", checker).protect("/secret"))
+        .any("/*", |_: &mut Request| {
+            Response::new(StatusCode::OK).with_body("ok")
+        })
+}
+
+fn status(router: &Router, path: &str, authorization: Option<&str>) -> StatusCode {
+    let mut head = format!("GET {} HTTP/1.1\r\nHost: a\r\n", path);
+    if let Some(value) = authorization {
+        head.push_str(&format!("Authorization: {}\r\n", value));
+    }
+    head.push_str("\r\n");
+    let mut request = Request::try_from(head.as_bytes()).unwrap();
+    router.handle_request(&mut request).status_code()
+}
+
+#[test]
+fn protects_encoded_and_unnormalized_paths() {
+    let router = router();
+    for path in [
+        "/secret",
+        "/secret/",
+        "/secret/x",
+        "/%73ecret/x",
+        "/%73%65%63%72%65%74",
+        "//secret/x",
+        "/./secret/x",
+        "/public/../secret/x",
+        "/%ZZ",
+    ] {
+        assert_eq!(
+            status(&router, path, None),
+            StatusCode::UNAUTHORIZED,
+            "{}",
+            path
+        );
+    }
+    // "ada:pw"
+    let valid = Some("Basic YWRhOnB3");
+    assert_eq!(status(&router, "/%73ecret/x", valid), StatusCode::OK);
+}
+
+#[test]
+fn matches_prefixes_at_segment_boundaries() {
+    let router = router();
+    for path in ["/", "/secretary", "/secret-plans/x", "/public/secret"] {
+        assert_eq!(status(&router, path, None), StatusCode::OK, "{}", path);
+    }
+}
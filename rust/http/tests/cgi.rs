@@ -0,0 +1,84 @@
+#![cfg(unix)]
+
+use http::handlers::Cgi;
+use http::http::{Request, Response, StatusCode};
+use http::server::Handler;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Writes `body` as an executable shell script in a directory of its own.
+fn script(body: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "http-cgi-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("script.sh");
+    fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn run(cgi: Cgi, request: &str) -> Response {
+    let mut request = Request::try_from(request.as_bytes()).unwrap();
+    cgi.handle_request(&mut request)
+}
+
+#[test]
+fn does_not_pass_the_proxy_header() {
+    let cgi = Cgi::new(script(
+        "printf 'Content-Type: text/plain\\n\\n[%s][%s]' \"$HTTP_PROXY\" \"$HTTP_X_OTHER\"",
+    ));
+    let response = run(
+        cgi,
+        "GET / HTTP/1.1\r\nHost: a\r\nProxy: http://evil:8080\r\nX-Other: kept\r\n\r\n",
+    );
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.body(), Some(&b"[][kept]"[..]));
+}
+
+#[test]
+fn caps_the_output() {
+    let cgi = Cgi::new(script(
+        "printf 'Content-Type: text/plain\\n\\n'; head -c 100000 /dev/zero",
+    ))
+    .max_output(1000);
+    let response = run(cgi, "GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+    assert_eq!(response.status_code(), StatusCode::BAD_GATEWAY);
+}
+
+#[test]
+fn runs_a_script_by_relative_path() {
+    // Relative to the current directory, which is where the configuration names it from.
+    let dir = PathBuf::from(format!("cgi-relative-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("report.sh");
+    fs::write(
+        &path,
+        "#!/bin/sh\nprintf 'Content-Type: text/plain\\n\\nreport'\n",
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let response = run(Cgi::new(&path), "GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(response.body(), Some(&b"report"[..]));
+}
+
+#[test]
+fn rejects_status_codes_out_of_range() {
+    for code in ["0", "99", "100", "101", "199", "600", "999"] {
+        let cgi = Cgi::new(script(&format!("printf 'Status: {} Odd\\n\\nbody'", code)));
+        let response = run(cgi, "GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert_eq!(response.status_code(), StatusCode::BAD_GATEWAY, "{}", code);
+    }
+
+    let cgi = Cgi::new(script("printf 'Status: 418 Teapot\\n\\nbody'"));
+    let response = run(cgi, "GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+    assert_eq!(response.status_code(), StatusCode(418));
+}
@@ -0,0 +1,180 @@
+use http::client::{Client, ClientError};
+use http::http::{Method, Request, Response, StatusCode};
+use http::router::Router;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+mod common;
+
+fn redirect(status: StatusCode, location: &'static str) -> impl Fn(&mut Request) -> Response {
+    move |_: &mut Request| Response::new(status).with_header("Location", location)
+}
+
+// Echoes what arrived: method, body and the credentials headers.
+fn echo(request: &mut Request) -> Response {
+    Response::new(StatusCode::OK).with_body(format!(
+        "{} {} auth={} cookie={}",
+        request.method,
+        String::from_utf8_lossy(&request.body),
+        request.headers.get("Authorization").unwrap_or("-"),
+        request.headers.get("Cookie").unwrap_or("-"),
+    ))
+}
+
+fn body(response: &Response) -> String {
+    String::from_utf8_lossy(response.body().unwrap_or_default()).into_owned()
+}
+
+#[test]
+fn follows_redirects() {
+    let addr = common::serve(
+        Router::new()
+            .any("/found", redirect(StatusCode::FOUND, "/dir/moved"))
+            .any("/dir/moved", redirect(StatusCode::MOVED_PERMANENTLY, "end"))
+            .any(
+                "/temporary",
+                redirect(StatusCode::TEMPORARY_REDIRECT, "/dir/end"),
+            )
+            .any("/loop", redirect(StatusCode::FOUND, "/loop"))
+            .any("/dir/end", echo),
+    );
+    let client = Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+
+    // 301, 302 and 303 turn a POST into a GET without a body; 307 and 308 keep both.
+    let response = client
+        .request(Method::POST, &url("/found"))
+        .body("data")
+        .send()
+        .unwrap();
+    assert_eq!(body(&response), "GET  auth=- cookie=-");
+    let response = client
+        .request(Method::POST, &url("/temporary"))
+        .body("data")
+        .send()
+        .unwrap();
+    assert_eq!(body(&response), "POST data auth=- cookie=-");
+
+    assert!(matches!(
+        client.get(&url("/loop")),
+        Err(ClientError::TooManyRedirects)
+    ));
+    let unfollowed = Client::new().max_redirects(0).get(&url("/found")).unwrap();
+    assert_eq!(unfollowed.status_code(), StatusCode::FOUND);
+}
+
+#[test]
+fn strips_credentials_on_cross_host_redirects() {
+    let other = common::serve(Router::new().any("/echo", echo));
+    let location: &'static str = Box::leak(format!("http://{}/echo", other).into_boxed_str());
+    let addr = common::serve(
+        Router::new()
+            .any("/away", redirect(StatusCode::FOUND, location))
+            .any("/here", redirect(StatusCode::FOUND, "/echo"))
+            .any("/echo", echo),
+    );
+    let client = Client::new();
+    let get = |path: &str| {
+        let response = client
+            .request(Method::GET, &format!("http://{}{}", addr, path))
+            .header("Authorization", "Bearer secret")
+            .header("Cookie", "session=1")
+            .send()
+            .unwrap();
+        body(&response)
+    };
+
+    assert_eq!(get("/here"), "GET  auth=Bearer secret cookie=session=1");
+    assert_eq!(get("/away"), "GET  auth=- cookie=-");
+}
+
+#[test]
+fn reuses_pooled_connections() {
+    let addr = common::serve(|request: &mut Request| {
+        Response::new(StatusCode::OK).with_body(request.remote_addr.unwrap().port().to_string())
+    });
+    let client = Client::new();
+    let url = format!("http://{}/", addr);
+    let first = body(&client.get(&url).unwrap());
+    let second = body(&client.get(&url).unwrap());
+    assert_eq!(first, second);
+}
+
+// A server that answers the first request on each connection and then reads one more and
+// closes without answering it, as if it timed out the idle connection mid-request. Returns
+// the request lines it received.
+fn hang_up_after_one() -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&received);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            if let Some(line) = read_request(&mut reader) {
+                log.lock().unwrap().push(line);
+                let _ = reader
+                    .get_mut()
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+            }
+            if let Some(line) = read_request(&mut reader) {
+                log.lock().unwrap().push(line);
+            }
+        }
+    });
+    (addr, received)
+}
+
+// The request line, after reading the rest of the head and the body.
+fn read_request(reader: &mut impl BufRead) -> Option<String> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None;
+    }
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        if line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok()?;
+            }
+        }
+    }
+    reader.take(length).read_to_end(&mut Vec::new()).ok()?;
+    Some(request_line.trim_end().to_string())
+}
+
+#[test]
+fn retries_idempotent_requests_on_stale_connections() {
+    let (addr, received) = hang_up_after_one();
+    let client = Client::new();
+    client.get(&format!("http://{}/a", addr)).unwrap();
+    let response = client.get(&format!("http://{}/b", addr)).unwrap();
+    assert_eq!(body(&response), "ok");
+    assert_eq!(
+        *received.lock().unwrap(),
+        ["GET /a HTTP/1.1", "GET /b HTTP/1.1", "GET /b HTTP/1.1"]
+    );
+}
+
+#[test]
+fn does_not_resend_a_post_on_a_stale_connection() {
+    let (addr, received) = hang_up_after_one();
+    let client = Client::new();
+    client.get(&format!("http://{}/a", addr)).unwrap();
+    let result = client
+        .request(Method::POST, &format!("http://{}/b", addr))
+        .body("once")
+        .send();
+    assert!(matches!(result, Err(ClientError::Io(_))));
+    assert_eq!(
+        *received.lock().unwrap(),
+        ["GET /a HTTP/1.1", "POST /b HTTP/1.1"]
+    );
+}
@@ -0,0 +1,74 @@
+// Fixtures shared by the integration tests; each test file uses only some of them.
+#![allow(dead_code)]
+
+use http::server::{Handler, Server, ServerHandle};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub fn local_server() -> Server {
+    Server::new(String::from("127.0.0.1"), 0)
+}
+
+// Runs `server` on its own thread and returns its addresses once all `listeners` are bound.
+pub fn start(
+    mut server: Server,
+    handler: impl Handler + 'static,
+    listeners: usize,
+) -> (ServerHandle, Vec<SocketAddr>) {
+    let handle = server.handle();
+    thread::spawn(move || server.run(handler).unwrap());
+    wait_for(handle, listeners)
+}
+
+// `start` on the tokio runtime.
+#[cfg(feature = "async")]
+pub fn start_async(
+    mut server: Server,
+    handler: impl Handler + 'static,
+    listeners: usize,
+) -> (ServerHandle, Vec<SocketAddr>) {
+    let handle = server.handle();
+    thread::spawn(move || server.run_async(handler).unwrap());
+    wait_for(handle, listeners)
+}
+
+fn wait_for(handle: ServerHandle, listeners: usize) -> (ServerHandle, Vec<SocketAddr>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while handle.local_addresses().len() < listeners {
+        assert!(Instant::now() < deadline, "server did not start");
+        thread::sleep(Duration::from_millis(10));
+    }
+    let addresses = handle.local_addresses();
+    (handle, addresses)
+}
+
+// One address per runtime the build has: threads, and tokio with the `async` feature.
+pub fn start_each<H: Handler + 'static>(
+    server: impl Fn() -> Server,
+    handler: impl Fn() -> H,
+) -> Vec<SocketAddr> {
+    #[allow(unused_mut)]
+    let mut addresses = vec![start(server(), handler(), 1).1[0]];
+    #[cfg(feature = "async")]
+    addresses.push(start_async(server(), handler(), 1).1[0]);
+    addresses
+}
+
+// Serves `handler` on an unused local port.
+pub fn serve(handler: impl Handler + 'static) -> SocketAddr {
+    start(local_server(), handler, 1).1[0]
+}
+
+// Everything the server sends in reply to `bytes` until it closes the connection.
+pub fn exchange(addr: SocketAddr, bytes: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(bytes).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
@@ -0,0 +1,265 @@
+use http::config::{Config, ConfigError, Runtime};
+use http::server::LogFormat;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// A directory for config files and the paths they name, removed when dropped.
+struct ConfigDir(PathBuf);
+
+impl ConfigDir {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "http-config-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        ConfigDir(dir)
+    }
+
+    fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for ConfigDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn vars(pairs: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn parse(toml: &str) -> Config {
+    toml::from_str(toml).unwrap()
+}
+
+fn error(result: Result<(), ConfigError>) -> String {
+    result.unwrap_err().to_string()
+}
+
+#[test]
+fn layers_the_file_environment_and_flags() {
+    let dir = ConfigDir::new();
+    let path = dir.write(
+        "http.toml",
+        "listen = [\"127.0.0.1:9000\"]\nworkers = 2\nkeep_alive_timeout = 7\nlog_format = \"json\"\n",
+    );
+    let mut config = Config::load(&path).unwrap();
+    config
+        .apply_env(vars(&[
+            ("HTTP_WORKERS", "4"),
+            ("HTTP_LISTEN", "127.0.0.1:9001, 127.0.0.1:9002"),
+            ("HTTP_CONFIG", "ignored.toml"),
+            ("WORKERS", "5"),
+        ]))
+        .unwrap();
+    config.set("workers", "8").unwrap();
+
+    assert_eq!(config.workers, Some(8));
+    assert_eq!(config.listen, ["127.0.0.1:9001", "127.0.0.1:9002"]);
+    assert_eq!(config.keep_alive_timeout, 7);
+    assert_eq!(config.log_format, LogFormat::Json);
+    config.validate().unwrap();
+}
+
+// The binary applies the same order: a valid flag rescues an invalid variable, which in
+// turn overrides a valid file.
+#[test]
+fn the_binary_reads_the_file_then_the_environment_then_flags() {
+    let dir = ConfigDir::new();
+    let invalid = dir.write("invalid.toml", "workers = 0\n");
+    let valid = dir.write("valid.toml", "workers = 3\n");
+    let check = |file: &Path, env_workers: Option<&str>, flags: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_http"));
+        command
+            .arg("--check-config")
+            .arg("--config")
+            .arg(file)
+            .args(flags)
+            .env_remove("HTTP_WORKERS")
+            .env_remove("HTTP_CONFIG");
+        if let Some(workers) = env_workers {
+            command.env("HTTP_WORKERS", workers);
+        }
+        let output = command.output().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let (code, stderr) = check(&invalid, None, &[]);
+    assert_eq!(code, Some(1));
+    assert!(
+        stderr.contains("invalid value \"0\" for `workers`"),
+        "{}",
+        stderr
+    );
+    assert_eq!(check(&invalid, Some("2"), &[]).0, Some(0));
+    assert_eq!(check(&valid, Some("0"), &[]).0, Some(1));
+    assert_eq!(check(&valid, Some("0"), &["--workers", "2"]).0, Some(0));
+    assert_eq!(check(&valid, None, &["--workers=0"]).0, Some(1));
+}
+
+#[test]
+fn explains_invalid_settings() {
+    let mut config = Config::default();
+    let cases = [
+        (
+            "workers",
+            "many",
+            "invalid value \"many\" for `workers`: expected a number",
+        ),
+        (
+            "log_format",
+            "xml",
+            "invalid value \"xml\" for `log_format`: expected `text` or `json`",
+        ),
+        (
+            "health_checks",
+            "yes",
+            "invalid value \"yes\" for `health_checks`: expected `true` or `false`",
+        ),
+        (
+            "runtime",
+            "fibers",
+            "invalid value \"fibers\" for `runtime`: expected `threads` or `async`",
+        ),
+        (
+            "rate_limit_burst",
+            "5000000000",
+            "invalid value \"5000000000\" for `rate_limit_burst`: is too large",
+        ),
+        ("verbose", "1", "unknown setting `verbose`"),
+    ];
+    for (setting, value, message) in cases {
+        assert_eq!(error(config.set(setting, value)), message);
+    }
+    assert_eq!(
+        error(config.apply_env(vars(&[("HTTP_RATE_LIMIT", "fast")]))),
+        "invalid value \"fast\" for `rate_limit`: expected a number"
+    );
+    // Unknown HTTP_ variables belong to someone else.
+    config
+        .apply_env(vars(&[("HTTP_PROXY", "http://proxy:3128")]))
+        .unwrap();
+}
+
+#[test]
+fn validates_the_rate_limit() {
+    let config = Config::default();
+    assert_eq!((config.rate_limit, config.rate_limit_burst), (10.0, 20));
+    config.validate().unwrap();
+    assert!(config.rate_limiter().is_some());
+
+    // 0 turns the limit off, and with it the need for a burst.
+    let off = parse("rate_limit = 0.0\nrate_limit_burst = 0\n");
+    off.validate().unwrap();
+    assert!(off.rate_limiter().is_none());
+
+    assert_eq!(
+        error(parse("rate_limit = 5.0\nrate_limit_burst = 0\n").validate()),
+        "invalid value \"0\" for `rate_limit_burst`: must be at least 1 while `rate_limit` is on"
+    );
+    assert_eq!(
+        error(parse("rate_limit = -1.0\n").validate()),
+        "invalid value \"-1\" for `rate_limit`: must be 0 or a positive number"
+    );
+    let mut config = Config::default();
+    config.set("rate_limit", "inf").unwrap();
+    assert_eq!(
+        error(config.validate()),
+        "invalid value \"inf\" for `rate_limit`: must be 0 or a positive number"
+    );
+}
+
+#[test]
+fn validates_paths() {
+    let dir = ConfigDir::new();
+    let script = dir.write("script.sh", "#!/bin/sh\n");
+    let root = dir.0.display();
+    let missing = dir.0.join("missing");
+    let missing = missing.display();
+
+    let valid = parse(&format!(
+        "static_root = \"{root}\"\n\
+         [[mount]]\npath = \"/files\"\nstatic = \"{root}\"\n\
+         [[mount]]\npath = \"/report\"\ncgi = \"{script}\"\n",
+        script = script.display(),
+    ));
+    valid.validate().unwrap();
+    assert_eq!(valid.mounts[1].timeout, 30);
+
+    let cases = [
+        (
+            format!("static_root = \"{}\"\n", missing),
+            format!("invalid value \"{}\" for `static_root`: is not a directory", missing),
+        ),
+        (
+            format!("[[mount]]\npath = \"/files\"\nstatic = \"{}\"\n", script.display()),
+            format!(
+                "invalid value \"{}\" for `mount[0].static`: is not a directory",
+                script.display()
+            ),
+        ),
+        (
+            format!("[[mount]]\npath = \"/report\"\ncgi = \"{}\"\n", root),
+            format!("invalid value \"{}\" for `mount[0].cgi`: is not a file", root),
+        ),
+        (
+            format!(
+                "[[mount]]\npath = \"/both\"\nstatic = \"{}\"\ncgi = \"{}\"\n",
+                root,
+                script.display()
+            ),
+            String::from("mount[0] (/both) needs exactly one of `static`, `proxy` or `cgi`"),
+        ),
+        (
+            format!("[[mount]]\npath = \"files/\"\nstatic = \"{}\"\n", root),
+            String::from(
+                "invalid value \"files/\" for `mount[0].path`: must start with `/` and not end with one",
+            ),
+        ),
+        (
+            format!(
+                "[[mount]]\npath = \"/a\"\nstatic = \"{root}\"\n[[mount]]\npath = \"/a\"\nstatic = \"{root}\"\n"
+            ),
+            String::from("invalid value \"/a\" for `mount[1].path`: mounted more than once"),
+        ),
+        (
+            String::from("metrics_path = \"/metrics\"\n"),
+            String::from(
+                "invalid value \"/metrics\" for `metrics_path`: is served on the admin listener, so `admin_listen` must be set",
+            ),
+        ),
+    ];
+    for (toml, message) in cases {
+        assert_eq!(error(parse(&toml).validate()), message, "{}", toml);
+    }
+}
+
+#[test]
+fn validates_the_runtime() {
+    let config = parse("runtime = \"async\"\n");
+    assert_eq!(config.runtime, Runtime::Async);
+    if cfg!(feature = "async") {
+        config.validate().unwrap();
+    } else {
+        assert_eq!(
+            error(config.validate()),
+            "invalid value \"async\" for `runtime`: this binary was built without the `async` feature"
+        );
+    }
+}
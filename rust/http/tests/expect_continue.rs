@@ -0,0 +1,118 @@
+use http::http::{Method, Request, Response, StatusCode};
+use http::middleware::{Auth, Credentials, Principal, Scheme};
+use http::router::Router;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+mod common;
+
+// The same routes on each runtime.
+fn servers() -> Vec<SocketAddr> {
+    common::start_each(|| common::local_server().max_body_size(1024), router)
+}
+
+fn router() -> Router {
+    let auth = Auth::new("uploads", |credentials: &Credentials| match credentials {
+        Credentials::Bearer { token } if token == "secret" => Some(Principal {
+            name: String::from("uploader"),
+            scheme: Scheme::Bearer,
+        }),
+        _ => None,
+    })
+    .protect("/private");
+    Router::new()
+        .with(auth)
+        .route(Method::POST, "/upload", |request: &mut Request| {
+            Response::new(StatusCode::OK).with_body(format!("{} bytes", request.body.len()))
+        })
+        .route(Method::POST, "/private/upload", |request: &mut Request| {
+            Response::new(StatusCode::OK).with_body(format!("{} bytes", request.body.len()))
+        })
+}
+
+// Sends a head and returns the connection plus the first status line the server answers with.
+fn send_head(addr: SocketAddr, head: &str) -> (BufReader<TcpStream>, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(head.as_bytes()).unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).unwrap();
+    (reader, status_line)
+}
+
+fn read_rest(reader: &mut BufReader<TcpStream>) -> String {
+    let mut rest = String::new();
+    reader.read_to_string(&mut rest).unwrap();
+    rest
+}
+
+#[test]
+fn continues_accepted_uploads() {
+    for addr in servers() {
+        let (mut reader, status_line) = send_head(
+            addr,
+            "POST /upload HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(status_line, "HTTP/1.1 100 Continue\r\n");
+        let mut blank = String::new();
+        reader.read_line(&mut blank).unwrap();
+        assert_eq!(blank, "\r\n");
+
+        reader.get_mut().write_all(b"hello").unwrap();
+        let rest = read_rest(&mut reader);
+        assert!(rest.starts_with("HTTP/1.1 200 OK\r\n"), "{}", rest);
+        assert!(rest.ends_with("5 bytes"), "{}", rest);
+    }
+}
+
+#[test]
+fn rejects_before_the_body() {
+    for addr in servers() {
+        let cases = [
+            ("POST /missing", 5, "HTTP/1.1 404 Not Found\r\n"),
+            ("GET /upload", 5, "HTTP/1.1 405 Method Not Allowed\r\n"),
+            ("POST /private/upload", 5, "HTTP/1.1 401 Unauthorized\r\n"),
+            ("POST /upload", 4096, "HTTP/1.1 413 Payload Too Large\r\n"),
+        ];
+        for (request_line, length, expected) in cases {
+            let head = format!(
+                "{} HTTP/1.1\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+                request_line, length
+            );
+            let (mut reader, status_line) = send_head(addr, &head);
+            assert_eq!(status_line, expected, "{}", request_line);
+            // The connection closes without waiting for the body.
+            let rest = read_rest(&mut reader);
+            assert!(rest.contains("Connection: close\r\n"), "{}", rest);
+        }
+    }
+}
+
+#[test]
+fn continues_authorized_uploads() {
+    for addr in servers() {
+        let (mut reader, status_line) = send_head(
+            addr,
+            "POST /private/upload HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 3\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(status_line, "HTTP/1.1 100 Continue\r\n");
+        reader.get_mut().write_all(b"abc").unwrap();
+        let rest = read_rest(&mut reader);
+        assert!(rest.ends_with("3 bytes"), "{}", rest);
+    }
+}
+
+#[test]
+fn rejects_unknown_expectations() {
+    for addr in servers() {
+        let (_, status_line) = send_head(
+            addr,
+            "POST /upload HTTP/1.1\r\nContent-Length: 5\r\nExpect: something-else\r\n\r\n",
+        );
+        assert_eq!(status_line, "HTTP/1.1 417 Expectation Failed\r\n");
+    }
+}
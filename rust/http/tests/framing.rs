@@ -0,0 +1,84 @@
+use http::http::{Method, Request, Response, StatusCode};
+use http::router::Router;
+use std::net::SocketAddr;
+
+mod common;
+
+// The same route on each runtime.
+fn servers() -> Vec<SocketAddr> {
+    common::start_each(common::local_server, || {
+        Router::new().route(Method::POST, "/", |request: &mut Request| {
+            Response::new(StatusCode::OK).with_body(format!("{} bytes", request.body.len()))
+        })
+    })
+}
+
+// A body the server frames differently from the client must not be read as a second request.
+#[test]
+fn refuses_ambiguous_bodies_and_closes() {
+    for addr in servers() {
+        let smuggled = "GET /smuggled HTTP/1.1\r\n\r\n";
+        let cases = [
+            (
+                format!(
+                    "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                    smuggled.len(),
+                    smuggled
+                ),
+                "HTTP/1.1 501 Not Implemented\r\n",
+            ),
+            (
+                format!(
+                    "POST / HTTP/1.1\r\nContent-Length: 0\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+                    smuggled
+                ),
+                "HTTP/1.1 400 Bad Request\r\n",
+            ),
+            (
+                format!(
+                    "POST / HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: {}\r\n\r\n{}",
+                    smuggled.len(),
+                    smuggled
+                ),
+                "HTTP/1.1 400 Bad Request\r\n",
+            ),
+        ];
+        for (request, status_line) in cases {
+            let response = common::exchange(addr, request.as_bytes());
+            assert!(response.starts_with(status_line), "{}", response);
+            assert_eq!(response.matches("HTTP/1.1").count(), 1, "{}", response);
+        }
+    }
+}
+
+#[test]
+fn keeps_content_length_bodies_alive() {
+    for addr in servers() {
+        let response = common::exchange(
+            addr,
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nhiPOST / HTTP/1.1\r\nContent-Length: 3\r\nConnection: close\r\n\r\nhey",
+        );
+        assert_eq!(
+            response.matches("HTTP/1.1 200 OK").count(),
+            2,
+            "{}",
+            response
+        );
+        assert!(response.ends_with("3 bytes"), "{}", response);
+    }
+}
+
+#[test]
+fn skips_blank_lines_and_caps_the_head() {
+    for addr in servers() {
+        let response = common::exchange(
+            addr,
+            b"\r\n\r\nPOST / HTTP/1.1\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi",
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+        let request = format!("GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "a".repeat(9000));
+        let response = common::exchange(addr, request.as_bytes());
+        assert!(response.starts_with("HTTP/1.1 431 "), "{}", response);
+    }
+}
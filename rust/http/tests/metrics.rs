@@ -0,0 +1,22 @@
+use http::http::{Method, StatusCode};
+use http::metrics::Metrics;
+use std::time::Duration;
+
+#[test]
+fn extension_methods_share_a_label() {
+    let metrics = Metrics::new();
+    for token in ["PROPFIND", "MKCOL", "XYZZY1", "XYZZY2"] {
+        let method = Method::Extension(String::from(token));
+        metrics.record_request(&method, "/", StatusCode::OK, Duration::ZERO);
+    }
+    metrics.record_request(&Method::GET, "/", StatusCode::OK, Duration::ZERO);
+
+    let rendered = metrics.render();
+    assert!(
+        rendered.contains("http_requests_total{method=\"OTHER\",route=\"/\",status=\"200\"} 4"),
+        "{}",
+        rendered
+    );
+    assert!(rendered.contains("method=\"GET\""), "{}", rendered);
+    assert!(!rendered.contains("PROPFIND"), "{}", rendered);
+}
@@ -0,0 +1,127 @@
+use http::handlers::Proxy;
+use http::http::{Method, Request, Response, StatusCode};
+use http::router::Router;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+mod common;
+
+// An upstream that echoes the method and body, behind a proxy; returns the proxy's address.
+fn start() -> SocketAddr {
+    let echo = |request: &mut Request| {
+        Response::new(StatusCode::OK).with_body(format!(
+            "{} {}",
+            request.method,
+            String::from_utf8_lossy(&request.body)
+        ))
+    };
+    let upstream = common::serve(
+        Router::new()
+            .route(Method::Extension(String::from("PROPFIND")), "/x", echo)
+            .route(Method::POST, "/x", echo),
+    );
+    common::serve(Proxy::new(&[&upstream.to_string()]))
+}
+
+#[test]
+fn forwards_extension_methods() {
+    let response = common::exchange(
+        start(),
+        b"PROPFIND /x HTTP/1.1\r\nHost: a\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("PROPFIND "), "{}", response);
+}
+
+#[test]
+fn answers_expect_continue_itself() {
+    let addr = start();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(
+            b"POST /x HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n",
+        )
+        .unwrap();
+    let mut interim = [0; 25];
+    stream.read_exact(&mut interim).unwrap();
+    assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+    stream.write_all(b"hello").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(!response.contains("100 Continue"), "{}", response);
+    assert!(response.ends_with("POST hello"), "{}", response);
+}
+
+#[test]
+fn skips_interim_upstream_responses() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 102 Processing\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfinal")
+            .unwrap();
+    });
+    let proxy = common::serve(Proxy::new(&[&upstream.to_string()]));
+
+    let response = common::exchange(
+        proxy,
+        b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("final"), "{}", response);
+}
+
+#[test]
+fn forwards_bodiless_statuses_without_a_length() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for status in ["304 Not Modified\r\nETag: \"v1\"", "204 No Content"] {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            write!(reader.get_mut(), "HTTP/1.1 {}\r\n\r\n", status).unwrap();
+        }
+    });
+    let proxy = common::serve(Proxy::new(&[&upstream.to_string()]));
+
+    for status in ["304 Not Modified", "204 No Content"] {
+        let response = common::exchange(
+            proxy,
+            b"GET / HTTP/1.1\r\nHost: a\r\nIf-None-Match: \"v1\"\r\nConnection: close\r\n\r\n",
+        );
+        assert!(
+            response.starts_with(&format!("HTTP/1.1 {}\r\n", status)),
+            "{}",
+            response
+        );
+        assert!(!response.contains("Content-Length"), "{}", response);
+        assert!(!response.contains("Transfer-Encoding"), "{}", response);
+        assert_eq!(
+            response.contains("ETag: \"v1\"\r\n"),
+            status.starts_with("304"),
+            "{}",
+            response
+        );
+        assert!(response.ends_with("\r\n\r\n"), "{}", response);
+    }
+}
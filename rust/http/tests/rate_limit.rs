@@ -0,0 +1,142 @@
+use http::connections::ConnectionTracker;
+use http::http::{Request, Response, StatusCode};
+use http::middleware::{KeyBy, Middleware, Next, Rate, RateLimit};
+use std::net::IpAddr;
+use std::thread;
+use std::time::Duration;
+
+fn ok(_: &mut Request) -> Response {
+    Response::new(StatusCode::OK)
+}
+
+fn send(limit: &RateLimit, path: &str, client: &str) -> Response {
+    let head = format!("GET {} HTTP/1.1\r\nX-Client: {}\r\n\r\n", path, client);
+    let mut request = Request::try_from(head.as_bytes()).unwrap();
+    request.remote_addr = Some("192.0.2.1:4000".parse().unwrap());
+    limit.handle(&mut request, Next::new(&[], &ok))
+}
+
+fn status(limit: &RateLimit, path: &str) -> StatusCode {
+    send(limit, path, "a").status_code()
+}
+
+#[test]
+fn refills_tokens_over_time() {
+    let limit = RateLimit::new(KeyBy::RemoteAddr).default_rate(Rate::new(20.0, 2));
+    assert_eq!(status(&limit, "/"), StatusCode::OK);
+    assert_eq!(status(&limit, "/"), StatusCode::OK);
+    assert_eq!(status(&limit, "/"), StatusCode::TOO_MANY_REQUESTS);
+
+    // One token every 50ms.
+    thread::sleep(Duration::from_millis(120));
+    assert_eq!(status(&limit, "/"), StatusCode::OK);
+    assert_eq!(status(&limit, "/"), StatusCode::OK);
+    assert_eq!(status(&limit, "/"), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn says_when_to_retry() {
+    let limit = RateLimit::new(KeyBy::RemoteAddr)
+        .default_rate(Rate::new(0.25, 1))
+        .route("/frozen", Rate::new(0.0, 1));
+    assert_eq!(status(&limit, "/"), StatusCode::OK);
+    let response = send(&limit, "/", "a");
+    assert_eq!(response.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers.get("Retry-After"), Some("4"));
+
+    // A rate of 0 never refills.
+    assert_eq!(status(&limit, "/frozen"), StatusCode::OK);
+    let response = send(&limit, "/frozen", "a");
+    assert_eq!(response.headers.get("Retry-After"), Some("3600"));
+}
+
+#[test]
+fn matches_routes_at_segment_boundaries() {
+    let limit = RateLimit::new(KeyBy::RemoteAddr)
+        .route("/api", Rate::new(0.0, 1))
+        .route("/api/bulk/", Rate::new(0.0, 2));
+
+    assert_eq!(status(&limit, "/api/users"), StatusCode::OK);
+    assert_eq!(status(&limit, "/api"), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        status(&limit, "/%61pi/users"),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(
+        status(&limit, "/docs/../api/"),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // Not below "/api", and without a default rate not limited at all.
+    for _ in 0..3 {
+        assert_eq!(status(&limit, "/apiary"), StatusCode::OK);
+        assert_eq!(status(&limit, "/"), StatusCode::OK);
+    }
+
+    // The longer prefix has a bucket of its own.
+    assert_eq!(status(&limit, "/api/bulk"), StatusCode::OK);
+    assert_eq!(status(&limit, "/api/bulk/upload"), StatusCode::OK);
+    assert_eq!(status(&limit, "/api/bulk"), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn keys_by_header() {
+    let limit =
+        RateLimit::new(KeyBy::Header(String::from("X-Client"))).default_rate(Rate::new(0.0, 1));
+    assert_eq!(send(&limit, "/", "a").status_code(), StatusCode::OK);
+    assert_eq!(send(&limit, "/", "b").status_code(), StatusCode::OK);
+    assert_eq!(
+        send(&limit, "/", "a").status_code(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[test]
+fn evicts_the_least_recently_seen_clients() {
+    // Matches MAX_TRACKED_BUCKETS in the middleware.
+    const TRACKED: usize = 10_000;
+    let limit =
+        RateLimit::new(KeyBy::Header(String::from("X-Client"))).default_rate(Rate::new(0.0, 1));
+    for client in 0..TRACKED {
+        send(&limit, "/", &client.to_string());
+    }
+
+    // The next client finds the map full, which evicts the least recently seen tenth.
+    assert_eq!(send(&limit, "/", "new").status_code(), StatusCode::OK);
+    for client in ["0", "999"] {
+        assert_eq!(send(&limit, "/", client).status_code(), StatusCode::OK);
+    }
+    for client in ["1000", "9999", "new"] {
+        assert_eq!(
+            send(&limit, "/", client).status_code(),
+            StatusCode::TOO_MANY_REQUESTS,
+            "{}",
+            client
+        );
+    }
+}
+
+#[test]
+fn caps_connections_overall_and_per_address() {
+    let a: IpAddr = "192.0.2.1".parse().unwrap();
+    let b: IpAddr = "192.0.2.2".parse().unwrap();
+    let c: IpAddr = "192.0.2.3".parse().unwrap();
+    let tracker = ConnectionTracker::new(Some(3), Some(2));
+
+    let first = tracker.try_acquire(a).unwrap();
+    let _second = tracker.try_acquire(a).unwrap();
+    assert!(tracker.try_acquire(a).is_none());
+    let _third = tracker.try_acquire(b).unwrap();
+    assert!(tracker.try_acquire(c).is_none());
+    assert_eq!(tracker.active(), 3);
+
+    drop(first);
+    assert_eq!(tracker.active(), 2);
+    let _fourth = tracker.try_acquire(c).unwrap();
+
+    // A tracker sharing the counts enforces its own caps.
+    let admin = tracker.with_limits(Some(4), None);
+    let _fifth = admin.try_acquire(a).unwrap();
+    assert!(admin.try_acquire(a).is_none());
+    assert_eq!(tracker.active(), 4);
+}
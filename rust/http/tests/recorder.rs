@@ -0,0 +1,221 @@
+use http::har::{Har, NameValue, REDACTED};
+use http::http::{Request, Response, StatusCode};
+use http::middleware::Recorder;
+use http::replay::{Difference, Outcome, Replay};
+use http::router::Router;
+use http::server::Handler;
+use std::fs;
+use std::io::Cursor;
+use std::time::Duration;
+
+mod common;
+
+#[test]
+fn saves_at_most_once_per_interval_and_on_drop() {
+    let path = std::env::temp_dir().join(format!("http-recorder-{}.har", std::process::id()));
+    let router = Router::new()
+        .with(
+            Recorder::new()
+                .save_to(&path)
+                .save_interval(Duration::from_secs(3600)),
+        )
+        .get("/", |_: &mut Request| Response::new(StatusCode::OK));
+
+    for _ in 0..3 {
+        let mut request = Request::try_from(&b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"[..]).unwrap();
+        router.handle_request(&mut request);
+    }
+    // The first exchange was written right away; the next two wait for the interval.
+    assert_eq!(Har::load(&path).unwrap().log.entries.len(), 1);
+
+    drop(router);
+    assert_eq!(Har::load(&path).unwrap().log.entries.len(), 3);
+    let _ = fs::remove_file(&path);
+}
+
+fn record(
+    recorder: Recorder,
+    request: &[u8],
+    response: impl Fn() -> Response + Send + Sync + 'static,
+) -> Har {
+    let recording = recorder.recording();
+    let router = Router::new()
+        .with(recorder)
+        .any("/page", move |_: &mut Request| response());
+    router.handle_request(&mut Request::try_from(request).unwrap());
+    recording.har()
+}
+
+fn header<'a>(headers: &'a [NameValue], name: &str) -> &'a str {
+    &headers
+        .iter()
+        .find(|header| header.name == name)
+        .unwrap()
+        .value
+}
+
+#[test]
+fn redacts_credentials_and_chosen_headers() {
+    let har = record(
+        Recorder::new().redact_header("x-api-*"),
+        b"GET /page HTTP/1.1\r\nHost: a\r\nAuthorization: Bearer secret\r\nCookie: s=1\r\nX-Api-Key: k\r\nX-API-Secret: s\r\nX-Apiary: bees\r\n\r\n",
+        || {
+            Response::new(StatusCode::OK)
+                .with_header("Set-Cookie", "s=2")
+                .with_header("X-Api-Token", "t")
+        },
+    );
+    let entry = &har.log.entries[0];
+    let request = &entry.request.headers;
+    for name in ["Authorization", "Cookie", "X-Api-Key", "X-API-Secret"] {
+        assert_eq!(header(request, name), REDACTED, "{}", name);
+    }
+    assert_eq!(header(request, "X-Apiary"), "bees");
+    assert_eq!(header(request, "Host"), "a");
+    let response = &entry.response.headers;
+    assert_eq!(header(response, "Set-Cookie"), REDACTED);
+    assert_eq!(header(response, "X-Api-Token"), REDACTED);
+}
+
+#[test]
+fn caps_recorded_bodies() {
+    let har = record(
+        Recorder::new().max_body_size(4),
+        b"POST /page HTTP/1.1\r\nHost: a\r\nContent-Length: 11\r\n\r\nhello world",
+        || Response::new(StatusCode::OK).with_body(vec![0xff, 0xfe, 0, 1, 2]),
+    );
+    let entry = &har.log.entries[0];
+    let post_data = entry.request.post_data.as_ref().unwrap();
+    assert_eq!(post_data.text, "hell");
+    assert_eq!(
+        post_data.comment.as_deref(),
+        Some("truncated to 4 of 11 bytes")
+    );
+    assert_eq!(entry.request.body_size, 11);
+    assert_eq!(entry.request.body(), None);
+
+    let content = &entry.response.content;
+    assert_eq!(content.text.as_deref(), Some("//4AAQ=="));
+    assert_eq!(content.encoding.as_deref(), Some("base64"));
+    assert_eq!(
+        content.comment.as_deref(),
+        Some("truncated to 4 of 5 bytes")
+    );
+    assert_eq!(content.size, 5);
+    assert_eq!(content.body(), None);
+
+    // Bodies within the limit decode back to what was sent.
+    let har = record(
+        Recorder::new().max_body_size(5),
+        b"POST /page HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello",
+        || Response::new(StatusCode::OK).with_body(vec![0xff, 0xfe, 0, 1, 2]),
+    );
+    let entry = &har.log.entries[0];
+    assert_eq!(entry.request.body().as_deref(), Some(&b"hello"[..]));
+    assert_eq!(
+        entry.response.content.body().as_deref(),
+        Some(&[0xff, 0xfe, 0, 1, 2][..])
+    );
+
+    let har = record(
+        Recorder::new(),
+        b"GET /page HTTP/1.1\r\nHost: a\r\n\r\n",
+        || Response::new(StatusCode::OK).with_reader(Cursor::new(b"streamed".to_vec()), None),
+    );
+    let content = &har.log.entries[0].response.content;
+    assert_eq!(
+        content.comment.as_deref(),
+        Some("streamed body not recorded")
+    );
+    assert_eq!(content.body(), None);
+}
+
+#[test]
+fn keeps_the_latest_entries() {
+    let recorder = Recorder::new().max_entries(2);
+    let recording = recorder.recording();
+    let router = Router::new()
+        .with(recorder)
+        .any("/*", |_: &mut Request| Response::new(StatusCode::OK));
+    for path in ["/1", "/2", "/3"] {
+        let head = format!("GET {} HTTP/1.1\r\nHost: a\r\n\r\n", path);
+        router.handle_request(&mut Request::try_from(head.as_bytes()).unwrap());
+    }
+    let urls: Vec<String> = recording
+        .har()
+        .log
+        .entries
+        .into_iter()
+        .map(|entry| entry.request.url)
+        .collect();
+    assert_eq!(urls, ["http://a/2", "http://a/3"]);
+}
+
+fn page(version: &'static str, request_id: &'static str, body: &'static str) -> Response {
+    Response::new(StatusCode::OK)
+        .with_header("Date", "Mon, 01 Jan 2024 00:00:00 GMT")
+        .with_header("X-Version", version)
+        .with_header("X-Request-Id", request_id)
+        .with_header("Set-Cookie", "session=recorded")
+        .with_body(body)
+}
+
+#[test]
+fn replays_and_reports_differences() {
+    let har = record(
+        Recorder::new(),
+        b"GET /page HTTP/1.1\r\nHost: a\r\n\r\n",
+        || page("1", "recorded", "one\ntwo\nthree"),
+    );
+    let entry = &har.log.entries[0];
+
+    // Date, the framing headers and the redacted cookie are left out of the comparison.
+    let same = common::serve(Router::new().any("/page", |_: &mut Request| {
+        page("1", "recorded", "one\ntwo\nthree").with_header("Set-Cookie", "session=new")
+    }));
+    let replay = Replay::new(&format!("http://{}", same)).unwrap();
+    assert!(matches!(replay.run(entry), Outcome::Matched));
+
+    let changed = common::serve(Router::new().any("/page", |_: &mut Request| {
+        page("2", "replayed", "one\nTWO\nthree")
+    }));
+    let replay = Replay::new(&format!("http://{}", changed))
+        .unwrap()
+        .ignore_header("x-request-id");
+    let differences = match replay.run(entry) {
+        Outcome::Differs(differences) => differences,
+        outcome => panic!("{:?}", outcome),
+    };
+    assert_eq!(
+        differences,
+        [
+            Difference::Header {
+                name: String::from("X-Version"),
+                recorded: Some(String::from("1")),
+                replayed: Some(String::from("2")),
+            },
+            Difference::Body {
+                recorded: b"one\ntwo\nthree".to_vec(),
+                replayed: b"one\nTWO\nthree".to_vec(),
+            },
+        ]
+    );
+    assert_eq!(
+        differences[1].to_string(),
+        "body differs at line 2:\n      - two\n      + TWO"
+    );
+}
+
+#[test]
+fn skips_truncated_requests() {
+    let har = record(
+        Recorder::new().max_body_size(2),
+        b"POST /page HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello",
+        || Response::new(StatusCode::OK),
+    );
+    let replay = Replay::new("http://127.0.0.1:1").unwrap();
+    assert!(matches!(
+        replay.run(&har.log.entries[0]),
+        Outcome::Skipped(reason) if reason == "request body was not fully recorded"
+    ));
+}
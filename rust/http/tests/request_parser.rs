@@ -0,0 +1,208 @@
+use http::http::{borrowed, Headers, Method, Request, RequestGenerator, StatusCode};
+use proptest::prelude::*;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+// Files named "ok-*" must parse and "err-*" must be rejected; none may panic. Inputs
+// that crash the parser or the fuzz target belong here.
+#[test]
+fn corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/request");
+    let mut count = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let bytes = fs::read(&path).unwrap();
+        let result = Request::try_from(&bytes[..]);
+        if name.starts_with("ok-") {
+            assert!(result.is_ok(), "{}: {:?}", name, result.err());
+        } else if name.starts_with("err-") {
+            assert!(result.is_err(), "{} parsed", name);
+        }
+        count += 1;
+    }
+    assert!(count > 0, "no corpus files in {}", dir.display());
+}
+
+#[test]
+fn multibyte_path() {
+    let request = Request::try_from("GET /ü/ß?q=é HTTP/1.1\r\n\r\n".as_bytes()).unwrap();
+    assert_eq!(request.path, "/ü/ß");
+    assert_eq!(request.query_string.as_deref(), Some("q=é"));
+}
+
+#[test]
+fn bare_line_feeds() {
+    let request =
+        Request::try_from(&b"POST /a HTTP/1.1\nHost: x\nContent-Length: 2\n\nhi"[..]).unwrap();
+    assert_eq!(request.method, Method::POST);
+    assert_eq!(request.headers.get("Host"), Some("x"));
+    assert_eq!(request.body, b"hi");
+}
+
+#[test]
+fn methods_round_trip() {
+    for token in ["GET", "PATCH", "PROPFIND", "MKCOL", "COPY", "MOVE", "get"] {
+        let method: Method = token.parse().unwrap();
+        assert_eq!(method.to_string(), token);
+    }
+    assert_eq!("DELETE".parse::<Method>().unwrap(), Method::DELETE);
+    assert_eq!(
+        "MKCOL".parse::<Method>().unwrap(),
+        Method::Extension(String::from("MKCOL"))
+    );
+
+    let error = "GE(T".parse::<Method>().unwrap_err();
+    assert_eq!(error.token(), "GE(T");
+    assert!("".parse::<Method>().is_err());
+}
+
+#[test]
+fn ambiguous_framing() {
+    let cases: [(&[u8], StatusCode); 4] = [
+        (
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            StatusCode::NOT_IMPLEMENTED,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\nTransfer-Encoding: chunked\r\n\r\nhi",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\nhi",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\nhi!",
+            StatusCode::BAD_REQUEST,
+        ),
+    ];
+    for (bytes, status) in cases {
+        let error = Request::try_from(bytes).unwrap_err();
+        assert_eq!(error.status_code(), status, "{:?}", error);
+        assert!(borrowed::Request::parse_head(bytes).is_err());
+    }
+}
+
+#[test]
+fn generated_requests_parse() {
+    for bytes in RequestGenerator::new(7).take(500) {
+        let request = borrowed::Request::parse(&bytes).unwrap();
+        assert_eq!(request.into_owned().to_bytes(), bytes);
+    }
+}
+
+fn method() -> impl Strategy<Value = Method> {
+    prop_oneof![
+        Just(Method::GET),
+        Just(Method::DELETE),
+        Just(Method::POST),
+        Just(Method::PUT),
+        Just(Method::HEAD),
+        Just(Method::CONNECT),
+        Just(Method::OPTIONS),
+        Just(Method::TRACE),
+        Just(Method::PATCH),
+        "[A-Za-z0-9!#$%&'*+.^_`|~-]{1,12}".prop_map(|token| token.parse().unwrap()),
+    ]
+}
+
+fn headers() -> impl Strategy<Value = Vec<(String, String)>> {
+    let name = "[A-Za-z0-9!#$%&'*+.^_`|~-]{1,20}"
+        .prop_filter("framing is generated from the body", |name| {
+            !name.eq_ignore_ascii_case("Content-Length")
+        });
+    let value = "([!-~]([ -~]{0,30}[!-~])?)?";
+    prop::collection::vec((name, value), 0..8)
+}
+
+fn request() -> impl Strategy<Value = Request> {
+    (
+        method(),
+        "/[A-Za-z0-9/._~%-]{0,40}",
+        prop::option::of("[A-Za-z0-9=&%._-]{0,30}"),
+        headers(),
+        prop::collection::vec(any::<u8>(), 0..256),
+    )
+        .prop_map(|(method, path, query_string, header_list, body)| {
+            let mut headers = Headers::new();
+            for (name, value) in &header_list {
+                headers.append(name, value);
+            }
+            Request {
+                path,
+                query_string,
+                method,
+                headers,
+                body,
+                remote_addr: None,
+                extensions: Default::default(),
+            }
+        })
+}
+
+proptest! {
+    #[test]
+    fn never_panics_on_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..1024)) {
+        let _ = Request::try_from(&bytes[..]);
+    }
+
+    // Mostly well formed heads with arbitrary text spliced in reach deeper than random bytes.
+    #[test]
+    fn never_panics_on_mangled_heads(
+        method in "\\PC{0,8}",
+        target in "\\PC{0,30}",
+        header in "\\PC{0,40}",
+        line_ending in prop_oneof![Just("\r\n"), Just("\n"), Just("\r")],
+        body in prop::collection::vec(any::<u8>(), 0..64),
+    ) {
+        let mut bytes = format!(
+            "{} {} HTTP/1.1{}{}{}{}",
+            method, target, line_ending, header, line_ending, line_ending
+        )
+        .into_bytes();
+        bytes.extend_from_slice(&body);
+        if let Ok(request) = Request::try_from(&bytes[..]) {
+            let reparsed = Request::try_from(&request.to_bytes()[..]).unwrap();
+            prop_assert_eq!(reparsed.to_bytes(), request.to_bytes());
+        }
+    }
+
+    #[test]
+    fn round_trips(request in request()) {
+        let bytes = request.to_bytes();
+        let parsed = Request::try_from(&bytes[..]).unwrap();
+        prop_assert_eq!(&parsed.method, &request.method);
+        prop_assert_eq!(&parsed.path, &request.path);
+        prop_assert_eq!(&parsed.query_string, &request.query_string);
+        prop_assert_eq!(&parsed.body, &request.body);
+        for (name, value) in request.headers.iter() {
+            prop_assert!(parsed.headers.get_all(name).any(|parsed| parsed == value));
+        }
+        prop_assert_eq!(parsed.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn borrowed_matches_owned(request in request()) {
+        let bytes = request.to_bytes();
+        let borrowed = borrowed::Request::parse(&bytes).unwrap();
+        prop_assert_eq!(borrowed.path, request.path.as_str());
+        prop_assert_eq!(borrowed.query_string, request.query_string.as_deref());
+        prop_assert_eq!(borrowed.body, &request.body[..]);
+        prop_assert_eq!(borrowed.into_owned().to_bytes(), bytes);
+    }
+
+    #[test]
+    fn bare_line_feeds_parse_like_crlf(request in request()) {
+        let bytes = request.to_bytes();
+        let head_end = bytes.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let mut lf = String::from_utf8(bytes[..head_end].to_vec())
+            .unwrap()
+            .replace("\r\n", "\n")
+            .into_bytes();
+        lf.extend_from_slice(&bytes[head_end..]);
+        let parsed = Request::try_from(&lf[..]).unwrap();
+        prop_assert_eq!(parsed.to_bytes(), bytes);
+    }
+}
@@ -0,0 +1,67 @@
+use http::http::Response;
+use http::sse::{Event, EventBroadcaster, EventStream};
+use std::thread;
+use std::time::Duration;
+
+// Reads the stream to its end, which comes once every sender is gone.
+fn body(mut response: Response) -> String {
+    response.buffer_body().unwrap();
+    String::from_utf8(response.body().unwrap().to_vec()).unwrap()
+}
+
+#[test]
+fn encodes_every_line_ending_as_a_new_data_line() {
+    let event = Event::new("a\r\nb\rc\nd\revent: admin")
+        .with_event("up\rdate\n")
+        .with_id("7\r\nretry: 1")
+        .with_retry(Duration::from_secs(2));
+    assert_eq!(
+        event.encode(),
+        "event: update\nid: 7retry: 1\nretry: 2000\ndata: a\ndata: b\ndata: c\ndata: d\ndata: event: admin\n\n"
+    );
+    assert_eq!(Event::new("").encode(), "data: \n\n");
+}
+
+#[test]
+fn replays_events_after_the_last_event_id() {
+    let broadcaster = EventBroadcaster::new(3);
+    for data in ["one", "two", "three", "four"] {
+        broadcaster.publish(Event::new(data));
+    }
+    // "one" has left the three-event buffer, so resuming from it replays nothing.
+    let resumed = broadcaster.subscribe(Some("2")).into_response();
+    let expired = broadcaster.subscribe(Some("1")).into_response();
+    let fresh = broadcaster.subscribe(None).into_response();
+    assert_eq!(broadcaster.subscriber_count(), 3);
+    broadcaster.publish(Event::new("five"));
+    // Dropping the only broadcaster ends every stream.
+    drop(broadcaster);
+
+    let resumed = body(resumed);
+    assert!(
+        resumed.contains("id: 3\ndata: three\n\nid: 4\ndata: four\n\nid: 5\ndata: five\n\n"),
+        "{}",
+        resumed
+    );
+    assert!(!resumed.contains("data: two"), "{}", resumed);
+    for stream in [body(expired), body(fresh)] {
+        assert!(!stream.contains("data: four"), "{}", stream);
+        assert!(stream.contains("id: 5\ndata: five\n\n"), "{}", stream);
+    }
+}
+
+#[test]
+fn sends_heartbeats_while_idle() {
+    let (sender, stream) = EventStream::channel();
+    let response = stream.heartbeat(Duration::from_millis(10)).into_response();
+    let publisher = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        sender.send(Event::new("late")).unwrap();
+    });
+    let output = body(response);
+    publisher.join().unwrap();
+
+    let heartbeat = output.find(": heartbeat\n\n").expect(&output);
+    let event = output.find("data: late\n\n").expect(&output);
+    assert!(heartbeat < event, "{}", output);
+}
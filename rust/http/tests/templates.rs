@@ -0,0 +1,172 @@
+use http::http::StatusCode;
+use http::templates::{Location, TemplateError, Templates};
+use serde_json::json;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+// A fresh directory holding `files`, removed again when the test ends.
+struct TemplateDir(PathBuf);
+
+impl TemplateDir {
+    fn new(files: &[(&str, &str)]) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "http-templates-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = TemplateDir(dir);
+        for (name, source) in files {
+            dir.write(name, source);
+        }
+        dir
+    }
+
+    fn write(&self, name: &str, source: &str) {
+        let path = self.0.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, source).unwrap();
+    }
+}
+
+impl Drop for TemplateDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn location(error: TemplateError) -> (Location, String) {
+    match error {
+        TemplateError::Syntax(location, message) | TemplateError::Render(location, message) => {
+            (location, message)
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn escapes_output() {
+    let dir = TemplateDir::new(&[("page.html", "<p>{{ name }}</p>{{ html | raw }}")]);
+    let templates = Templates::new(&dir.0);
+    let html = templates
+        .render(
+            "page.html",
+            &json!({ "name": "<b>\"Tom\" & 'Jerry'</b>", "html": "<i>ok</i>" }),
+        )
+        .unwrap();
+    assert_eq!(
+        html,
+        "<p>&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;</p><i>ok</i>"
+    );
+}
+
+#[test]
+fn conditionals_loops_and_includes() {
+    let dir = TemplateDir::new(&[
+        (
+            "list.html",
+            "{% include \"partials/header.html\" %}\
+             <ul>\n\
+             {% for user in users %}\n\
+             <li>{{ loop.index }}. {{ user.name }}{% if user.admin %} (admin){% endif %}\
+             {% if not loop.last %},{% endif %}</li>\n\
+             {% endfor %}\n\
+             </ul>\n\
+             {% if users %}{% else %}nobody{% endif %}",
+        ),
+        ("partials/header.html", "<h1>{{ title }}</h1>\n"),
+    ]);
+    let templates = Templates::new(&dir.0);
+    let context = json!({
+        "title": "Users",
+        "users": [{ "name": "ada", "admin": true }, { "name": "bob", "admin": false }],
+    });
+    assert_eq!(
+        templates.render("list.html", &context).unwrap(),
+        "<h1>Users</h1>\n<ul>\n<li>1. ada (admin),</li>\n<li>2. bob</li>\n</ul>\n"
+    );
+
+    let empty = json!({ "title": "Users", "users": [] });
+    assert!(templates
+        .render("list.html", &empty)
+        .unwrap()
+        .ends_with("</ul>\nnobody"));
+}
+
+#[test]
+fn reports_error_positions() {
+    let dir = TemplateDir::new(&[
+        ("unclosed.html", "<ul>\n  {% for item in items %}\n<li>"),
+        ("unknown.html", "a\nb {% while x %}"),
+        ("undefined.html", "<p>\n  <b>{{ user.nmae }}</b>"),
+        ("include.html", "x\n{% include \"missing.html\" %}"),
+    ]);
+    let templates = Templates::new(&dir.0);
+    let context = json!({ "items": [], "user": { "name": "ada" } });
+
+    let (at, message) = location(templates.render("unclosed.html", &context).unwrap_err());
+    assert_eq!(
+        (at.file.as_str(), at.line, at.column),
+        ("unclosed.html", 2, 3)
+    );
+    assert_eq!(message, "unclosed for");
+
+    let (at, message) = location(templates.render("unknown.html", &context).unwrap_err());
+    assert_eq!((at.line, at.column), (2, 3));
+    assert_eq!(message, "unknown tag \"while\"");
+
+    let error = templates.render("undefined.html", &context).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "undefined.html:2:6: undefined variable user.nmae"
+    );
+
+    let (at, _) = location(templates.render("include.html", &context).unwrap_err());
+    assert_eq!((at.line, at.column), (2, 1));
+
+    assert!(matches!(
+        templates.render("../secret.html", &context),
+        Err(TemplateError::InvalidName(_))
+    ));
+}
+
+#[test]
+fn reloads_changed_templates() {
+    let dir = TemplateDir::new(&[("page.html", "one")]);
+    let cached = Templates::new(&dir.0).reload(false);
+    let reloading = Templates::new(&dir.0).reload(true);
+    assert_eq!(cached.render("page.html", &json!({})).unwrap(), "one");
+    assert_eq!(reloading.render("page.html", &json!({})).unwrap(), "one");
+
+    dir.write("page.html", "two");
+    // Push the modification time forward in case the filesystem's resolution is coarse.
+    File::options()
+        .write(true)
+        .open(dir.0.join("page.html"))
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(cached.render("page.html", &json!({})).unwrap(), "one");
+    assert_eq!(reloading.render("page.html", &json!({})).unwrap(), "two");
+}
+
+#[test]
+fn renders_responses() {
+    let dir = TemplateDir::new(&[("ok.html", "hi {{ name }}"), ("bad.html", "{{ nope }}")]);
+    let templates = Templates::new(&dir.0);
+
+    let response = templates.response(StatusCode::OK, "ok.html", &json!({ "name": "ada" }));
+    assert_eq!(response.status_code(), StatusCode::OK);
+    assert_eq!(
+        response.headers.get("Content-Type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(response.body(), Some(&b"hi ada"[..]));
+
+    let response = templates.response(StatusCode::OK, "bad.html", &json!({}));
+    assert_eq!(response.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "tls")]
+
+use http::http::{Request, Response, StatusCode};
+use http::server::Server;
+use http::tls::{Certificate, TlsConfig};
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+
+fn hello(request: &mut Request) -> Response {
+    Response::new(StatusCode::OK).with_body(format!("hello {}", request.path))
+}
+
+struct SelfSigned {
+    certificate: Certificate,
+    der: CertificateDer<'static>,
+}
+
+fn self_signed(names: &[&str]) -> SelfSigned {
+    let names = names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    let generated = rcgen::generate_simple_self_signed(names).unwrap();
+    let certificate = Certificate::from_pem(
+        generated.cert.pem().as_bytes(),
+        generated.key_pair.serialize_pem().as_bytes(),
+    )
+    .unwrap();
+    SelfSigned {
+        certificate,
+        der: generated.cert.der().clone(),
+    }
+}
+
+fn https_get(
+    addr: SocketAddr,
+    name: &str,
+    trusted: &[&CertificateDer<'static>],
+) -> (String, CertificateDer<'static>) {
+    let mut roots = RootCertStore::empty();
+    for der in trusted {
+        roots.add((*der).clone()).unwrap();
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(name.to_string()).unwrap();
+    let connection = ClientConnection::new(Arc::new(config), name).unwrap();
+    let mut stream = StreamOwned::new(connection, TcpStream::connect(addr).unwrap());
+
+    stream
+        .write_all(b"GET /secure HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let presented = stream.conn.peer_certificates().unwrap()[0].clone();
+    (response, presented)
+}
+
+#[test]
+fn serves_https() {
+    let localhost = self_signed(&["localhost"]);
+    let server = Server::new(String::from("127.0.0.1"), 0)
+        .tls(TlsConfig::new(localhost.certificate.clone()));
+    let (handle, addresses) = common::start(server, hello, 1);
+
+    let (response, presented) = https_get(addresses[0], "localhost", &[&localhost.der]);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with("hello /secure"), "{}", response);
+    assert_eq!(presented, localhost.der);
+
+    // A plain HTTP request is not a TLS handshake, so it gets no response.
+    let mut stream = TcpStream::connect(addresses[0]).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    assert!(!response.starts_with(b"HTTP/1.1"));
+
+    handle.shutdown();
+}
+
+#[test]
+fn selects_certificate_by_server_name() {
+    let fallback = self_signed(&["localhost", "example.test"]);
+    let api = self_signed(&["api.example.test"]);
+    let wildcard = self_signed(&["*.example.test"]);
+    let tls = TlsConfig::new(fallback.certificate.clone())
+        .host("api.example.test", api.certificate.clone())
+        .host("*.example.test", wildcard.certificate.clone());
+    let server = Server::new(String::from("127.0.0.1"), 0).tls(tls);
+    let (handle, addresses) = common::start(server, hello, 1);
+    let trusted = [&fallback.der, &api.der, &wildcard.der];
+
+    let (_, presented) = https_get(addresses[0], "api.example.test", &trusted);
+    assert_eq!(presented, api.der);
+    let (_, presented) = https_get(addresses[0], "WWW.Example.Test", &trusted);
+    assert_eq!(presented, wildcard.der);
+    let (_, presented) = https_get(addresses[0], "localhost", &trusted);
+    assert_eq!(presented, fallback.der);
+    // Names resolve through the same table as virtual hosts: exact names first, and a
+    // wildcard covers subdomains but not the name it is under.
+    let (_, presented) = https_get(addresses[0], "API.example.test", &trusted);
+    assert_eq!(presented, api.der);
+    let (_, presented) = https_get(addresses[0], "example.test", &trusted);
+    assert_eq!(presented, fallback.der);
+
+    handle.shutdown();
+}
+
+#[test]
+fn redirects_plain_http() {
+    let localhost = self_signed(&["localhost"]);
+    let server = Server::new(String::from("127.0.0.1"), 0)
+        .tls(TlsConfig::new(localhost.certificate))
+        .redirect_http("127.0.0.1:0");
+    let (handle, addresses) = common::start(server, hello, 2);
+    let https_port = addresses[0].port();
+
+    let response = common::exchange(
+        addresses[1],
+        b"GET /docs/page?lang=en HTTP/1.1\r\nHost: localhost:8080\r\nConnection: close\r\n\r\n",
+    );
+    assert!(
+        response.starts_with("HTTP/1.1 308 Permanent Redirect\r\n"),
+        "{}",
+        response
+    );
+    let location = format!(
+        "Location: https://localhost:{}/docs/page?lang=en\r\n",
+        https_port
+    );
+    assert!(response.contains(&location), "{}", response);
+
+    let response = common::exchange(addresses[1], b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+        "{}",
+        response
+    );
+
+    handle.shutdown();
+}
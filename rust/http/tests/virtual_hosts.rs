@@ -0,0 +1,88 @@
+use http::http::{Request, Response, StatusCode};
+use http::server::Handler;
+use http::virtual_hosts::VirtualHosts;
+
+fn site(name: &'static str) -> impl Fn(&mut Request) -> Response {
+    move |_: &mut Request| Response::new(StatusCode::OK).with_body(name)
+}
+
+fn request(host: Option<&str>) -> Request {
+    let head = match host {
+        Some(host) => format!("GET / HTTP/1.1\r\nHost: {}\r\n\r\n", host),
+        None => String::from("GET / HTTP/1.1\r\n\r\n"),
+    };
+    Request::try_from(head.as_bytes()).unwrap()
+}
+
+// The site that answered, or the status when none did.
+fn serve(hosts: &VirtualHosts, host: Option<&str>) -> String {
+    let response = hosts.handle_request(&mut request(host));
+    match response.status_code() {
+        StatusCode::OK => String::from_utf8_lossy(response.body().unwrap()).into_owned(),
+        status => status.to_string(),
+    }
+}
+
+fn sites() -> VirtualHosts {
+    VirtualHosts::new()
+        .host("api.example.com", site("api"))
+        .host("*.example.com", site("wildcard"))
+        .host("*.eu.example.com", site("eu"))
+        .host("WWW.Example.org", site("org"))
+        .host("[::1]", site("ipv6"))
+}
+
+#[test]
+fn prefers_exact_names_then_the_longest_wildcard() {
+    let hosts = sites();
+    assert_eq!(serve(&hosts, Some("api.example.com")), "api");
+    assert_eq!(serve(&hosts, Some("www.example.com")), "wildcard");
+    assert_eq!(serve(&hosts, Some("a.b.example.com")), "wildcard");
+    assert_eq!(serve(&hosts, Some("paris.eu.example.com")), "eu");
+    assert_eq!(serve(&hosts, Some("eu.example.com")), "wildcard");
+    // A wildcard covers subdomains only.
+    assert_eq!(serve(&hosts, Some("example.com")), "404");
+    assert_eq!(serve(&hosts, Some("notexample.com")), "404");
+}
+
+#[test]
+fn strips_ports() {
+    let hosts = sites();
+    assert_eq!(serve(&hosts, Some("api.example.com:8080")), "api");
+    assert_eq!(serve(&hosts, Some("www.example.com:443")), "wildcard");
+    assert_eq!(serve(&hosts, Some("[::1]:8080")), "ipv6");
+    assert_eq!(serve(&hosts, Some("[::1]")), "ipv6");
+}
+
+#[test]
+fn compares_names_case_insensitively() {
+    let hosts = sites();
+    assert_eq!(serve(&hosts, Some("API.Example.COM")), "api");
+    assert_eq!(serve(&hosts, Some("www.example.org")), "org");
+    assert_eq!(serve(&hosts, Some("Paris.EU.example.com")), "eu");
+    // A fully qualified name may end with a dot.
+    assert_eq!(serve(&hosts, Some("api.example.com.")), "api");
+}
+
+#[test]
+fn falls_back_for_unknown_and_missing_hosts() {
+    let hosts = sites();
+    assert_eq!(serve(&hosts, None), "404");
+    let hosts = sites().unknown_status(StatusCode::MISDIRECTED_REQUEST);
+    assert_eq!(serve(&hosts, Some("other.net")), "421");
+
+    let hosts = sites().fallback(site("default"));
+    assert_eq!(serve(&hosts, Some("other.net")), "default");
+    assert_eq!(serve(&hosts, None), "default");
+    assert_eq!(serve(&hosts, Some("api.example.com")), "api");
+}
+
+#[test]
+fn refuses_unknown_hosts_before_the_body() {
+    let hosts = sites();
+    let response = hosts.check_head(&mut request(Some("other.net"))).unwrap();
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    assert!(hosts
+        .check_head(&mut request(Some("api.example.com")))
+        .is_none());
+}
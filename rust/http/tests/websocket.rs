@@ -0,0 +1,272 @@
+use http::websocket::{accept_key, Message, WebSocket, WebSocketHandler, CLOSE_PROTOCOL_ERROR};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+mod common;
+
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CONTINUATION: u8 = 0x0;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+// Sends every data message back until the peer closes or breaks the protocol.
+fn echo(mut socket: WebSocket) {
+    while let Ok(message) = socket.recv() {
+        match message {
+            Message::Text(_) | Message::Binary(_) => socket.send(&message).unwrap(),
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) => {}
+        }
+    }
+}
+
+// Completes the handshake and returns the connection with the server's response head.
+fn connect(addr: SocketAddr) -> (BufReader<TcpStream>, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    write!(
+        stream,
+        "GET /chat HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        KEY
+    )
+    .unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        head.push_str(&line);
+        if line == "\r\n" {
+            return (reader, head);
+        }
+    }
+}
+
+fn header(length: usize) -> Vec<u8> {
+    match length {
+        length if length < 126 => vec![length as u8],
+        length if length <= u16::MAX as usize => {
+            let mut bytes = vec![126];
+            bytes.extend_from_slice(&(length as u16).to_be_bytes());
+            bytes
+        }
+        length => {
+            let mut bytes = vec![127];
+            bytes.extend_from_slice(&(length as u64).to_be_bytes());
+            bytes
+        }
+    }
+}
+
+// Writes a frame masked as clients must.
+fn send(reader: &mut BufReader<TcpStream>, fin: bool, opcode: u8, payload: &[u8]) {
+    let mask = [0x37, 0xfa, 0x21, 0x3d];
+    let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+    let mut length = header(payload.len());
+    length[0] |= 0x80;
+    frame.extend_from_slice(&length);
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    reader.get_mut().write_all(&frame).unwrap();
+}
+
+fn send_unmasked(reader: &mut BufReader<TcpStream>, opcode: u8, payload: &[u8]) {
+    let mut frame = vec![0x80 | opcode];
+    frame.extend_from_slice(&header(payload.len()));
+    frame.extend_from_slice(payload);
+    reader.get_mut().write_all(&frame).unwrap();
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    // The 7-bit length field: the length itself, or 126 or 127 for the longer encodings.
+    length_code: u8,
+    payload: Vec<u8>,
+}
+
+// Reads a server frame, which must not be masked.
+fn receive(reader: &mut BufReader<TcpStream>) -> Frame {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).unwrap();
+    assert_eq!(head[1] & 0x80, 0, "server frames are not masked");
+    let length_code = head[1] & 0x7F;
+    let length = match length_code {
+        126 => {
+            let mut bytes = [0; 2];
+            reader.read_exact(&mut bytes).unwrap();
+            u16::from_be_bytes(bytes) as usize
+        }
+        127 => {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes).unwrap();
+            u64::from_be_bytes(bytes) as usize
+        }
+        length => length as usize,
+    };
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload).unwrap();
+    Frame {
+        fin: head[0] & 0x80 != 0,
+        opcode: head[0] & 0x0F,
+        length_code,
+        payload,
+    }
+}
+
+fn close_code(frame: &Frame) -> u16 {
+    assert_eq!(frame.opcode, CLOSE);
+    u16::from_be_bytes([frame.payload[0], frame.payload[1]])
+}
+
+fn assert_closed(reader: &mut BufReader<TcpStream>) {
+    let mut rest = Vec::new();
+    assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn completes_the_handshake() {
+    let addr = common::serve(WebSocketHandler::new(|socket, _| echo(socket)));
+    let (_, head) = connect(addr);
+    assert!(
+        head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+        "{}",
+        head
+    );
+    assert!(head.contains("Upgrade: websocket\r\n"), "{}", head);
+    assert!(head.contains("Connection: Upgrade\r\n"), "{}", head);
+    assert!(!head.contains("close"), "{}", head);
+    assert_eq!(accept_key(KEY), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    assert!(
+        head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"),
+        "{}",
+        head
+    );
+}
+
+#[test]
+fn unmasks_and_echoes_each_length_encoding() {
+    let addr =
+        common::serve(WebSocketHandler::new(|socket, _| echo(socket)).max_frame_size(1024 * 1024));
+    let (mut reader, _) = connect(addr);
+    for (length, code) in [(0, 0), (125, 125), (126, 126), (65535, 126), (70000, 127)] {
+        let payload: Vec<u8> = (0..length).map(|i| i as u8).collect();
+        send(&mut reader, true, BINARY, &payload);
+        let frame = receive(&mut reader);
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, BINARY);
+        assert_eq!(frame.length_code, code, "{} bytes", length);
+        assert!(frame.payload == payload, "{} bytes", length);
+    }
+}
+
+#[test]
+fn joins_continuation_frames_around_control_frames() {
+    let addr = common::serve(WebSocketHandler::new(|socket, _| echo(socket)));
+    let (mut reader, _) = connect(addr);
+    send(&mut reader, false, TEXT, b"Hel");
+    send(&mut reader, true, PING, b"still there?");
+    send(&mut reader, true, CONTINUATION, b"lo");
+
+    let pong = receive(&mut reader);
+    assert_eq!(pong.opcode, PONG);
+    assert_eq!(pong.payload, b"still there?");
+    let text = receive(&mut reader);
+    assert_eq!((text.fin, text.opcode), (true, TEXT));
+    assert_eq!(text.payload, b"Hello");
+}
+
+#[test]
+fn fragments_large_outgoing_messages() {
+    let addr = common::serve(
+        WebSocketHandler::new(|mut socket, _| socket.send_text("abcdefghij").unwrap())
+            .max_frame_size(4),
+    );
+    let (mut reader, _) = connect(addr);
+    let frames: Vec<(bool, u8, Vec<u8>)> = (0..3)
+        .map(|_| {
+            let frame = receive(&mut reader);
+            (frame.fin, frame.opcode, frame.payload)
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            (false, TEXT, b"abcd".to_vec()),
+            (false, CONTINUATION, b"efgh".to_vec()),
+            (true, CONTINUATION, b"ij".to_vec()),
+        ]
+    );
+}
+
+// Sends something the server must refuse.
+type Violation = fn(&mut BufReader<TcpStream>);
+
+#[test]
+fn refuses_protocol_violations() {
+    let addr = common::serve(WebSocketHandler::new(|socket, _| echo(socket)));
+    let cases: [(&str, Violation); 5] = [
+        ("unmasked frame", |reader| {
+            send_unmasked(reader, TEXT, b"hi")
+        }),
+        ("control frame over 125 bytes", |reader| {
+            send(reader, true, PING, &[0; 126])
+        }),
+        ("fragmented control frame", |reader| {
+            send(reader, false, PING, b"")
+        }),
+        ("continuation without a start", |reader| {
+            send(reader, true, CONTINUATION, b"x")
+        }),
+        ("new message inside a fragmented one", |reader| {
+            send(reader, false, TEXT, b"a");
+            send(reader, true, TEXT, b"b");
+        }),
+    ];
+    for (case, violate) in cases {
+        let (mut reader, _) = connect(addr);
+        violate(&mut reader);
+        assert_eq!(
+            close_code(&receive(&mut reader)),
+            CLOSE_PROTOCOL_ERROR,
+            "{}",
+            case
+        );
+        assert_closed(&mut reader);
+    }
+}
+
+#[test]
+fn answers_a_client_close() {
+    let addr = common::serve(WebSocketHandler::new(|socket, _| echo(socket)));
+    let (mut reader, _) = connect(addr);
+    let mut payload = 1000u16.to_be_bytes().to_vec();
+    payload.extend_from_slice(b"bye");
+    send(&mut reader, true, CLOSE, &payload);
+
+    let frame = receive(&mut reader);
+    assert_eq!(close_code(&frame), 1000);
+    assert_eq!(frame.payload.len(), 2);
+    assert_closed(&mut reader);
+}
+
+#[test]
+fn waits_for_the_answer_to_a_server_close() {
+    let addr = common::serve(WebSocketHandler::new(|mut socket, _| {
+        socket.close(1001, "going away").unwrap();
+    }));
+    let (mut reader, _) = connect(addr);
+    let frame = receive(&mut reader);
+    assert_eq!(close_code(&frame), 1001);
+    assert_eq!(&frame.payload[2..], b"going away");
+
+    send(&mut reader, true, CLOSE, &1001u16.to_be_bytes());
+    assert_closed(&mut reader);
+}
@@ -0,0 +1,438 @@
+use crate::content::catalog::Id;
+use crate::content::media::Kind;
+use crate::content::metadata::{Length, Rating, Timestamp};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: media [OPTIONS] <COMMAND>
+
+Commands:
+  add [KIND] [FIELDS]          Add an item, asking for missing required fields
+  list [--kind <KIND>]...      List items in the order they were added
+  show <ID>                    Show one item
+  search <WORDS>... [--kind <KIND>]... [--limit <N>]
+  edit <ID> [FIELDS]           Change the given fields of an item
+  remove <ID>                  Remove an item
+  import <FILE.csv>            Add every valid row of a CSV file, - for stdin
+  export [FILE.csv]            Write the catalog as CSV, to stdout without a file
+  checkout <ID> <BORROWER> [--due <DATE> | --days <N>]
+                               Lend a book, due in 14 days unless given
+  checkin <ID>                 Return a book
+  loans [--overdue] [--borrower <NAME>]
+                               List books out on loan, or a borrower's history
+
+Options:
+  -c, --catalog <FILE>         Catalog file (or MEDIA_CATALOG), default catalog.json
+      --format <table|json>    Output format, default table
+  -h, --help                   Print this help
+
+Fields:
+      --title <TEXT>           Title, or episode title for podcasts
+      --author <NAME>          Books and audiobooks
+      --director <NAME>        Movies
+      --narrator <NAME>        Audiobooks
+      --show <NAME>            Podcasts
+      --episode <N>            Podcasts
+      --year <YEAR>
+      --genre <GENRE>          Repeatable; replaces the item's genres
+      --tag <TAG>              Repeatable; replaces the item's tags
+      --rating <1-5>
+      --minutes <N>            Running time
+      --pages <N>              Page count
+
+KIND is book, movie, audiobook, podcast or placeholder. DATE is YYYY-MM-DD;
+a book is due by the end of that day (UTC).
+
+Exit status is 0 on success, 1 when nothing matched (an unknown id or no
+search results), 2 for invalid arguments, 3 when the catalog cannot be read or
+written, 4 when a loan is not possible (the item is not a book, is already
+out, or is not out when checked in) and 5 when an import skipped rejected rows
+but added the rest.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+}
+
+// Values given on the command line for `add` and `edit`; None means not given.
+#[derive(Debug, Default)]
+pub struct Fields {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub director: Option<String>,
+    pub narrator: Option<String>,
+    pub show: Option<String>,
+    pub episode: Option<u32>,
+    pub year: Option<u16>,
+    pub genres: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub rating: Option<Rating>,
+    pub length: Option<Length>,
+}
+
+impl Fields {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.author.is_none()
+            && self.director.is_none()
+            && self.narrator.is_none()
+            && self.show.is_none()
+            && self.episode.is_none()
+            && self.year.is_none()
+            && self.genres.is_none()
+            && self.tags.is_none()
+            && self.rating.is_none()
+            && self.length.is_none()
+    }
+}
+
+// When a checked out book is due back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Due {
+    On(Timestamp),
+    InDays(u32),
+}
+
+impl Default for Due {
+    fn default() -> Self {
+        Due::InDays(14)
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Add {
+        kind: Option<Kind>,
+        fields: Fields,
+    },
+    List {
+        kinds: Vec<Kind>,
+    },
+    Show {
+        id: Id,
+    },
+    Search {
+        words: Vec<String>,
+        kinds: Vec<Kind>,
+        limit: Option<usize>,
+    },
+    Edit {
+        id: Id,
+        fields: Fields,
+    },
+    Remove {
+        id: Id,
+    },
+    Import {
+        file: PathBuf,
+    },
+    Export {
+        file: Option<PathBuf>,
+    },
+    CheckOut {
+        id: Id,
+        borrower: String,
+        due: Due,
+    },
+    CheckIn {
+        id: Id,
+    },
+    Loans {
+        borrower: Option<String>,
+        overdue: bool,
+    },
+}
+
+impl Command {
+    // Whether the command saves the catalog, and so has to lock it.
+    pub fn changes_catalog(&self) -> bool {
+        match self {
+            Command::Add { .. }
+            | Command::Edit { .. }
+            | Command::Remove { .. }
+            | Command::Import { .. }
+            | Command::CheckOut { .. }
+            | Command::CheckIn { .. } => true,
+            Command::List { .. }
+            | Command::Show { .. }
+            | Command::Search { .. }
+            | Command::Export { .. }
+            | Command::Loans { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub catalog: Option<PathBuf>,
+    pub format: Format,
+    pub help: bool,
+    pub command: Option<Command>,
+}
+
+impl Options {
+    // Options may come before or after the command name.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut positional: Vec<String> = Vec::new();
+        let mut fields = Fields::default();
+        let mut kinds = Vec::new();
+        let mut limit = None;
+        let mut due = None;
+        let mut borrower = None;
+        let mut overdue = false;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |flag: &str| match &inline_value {
+                Some(value) => Ok(value.clone()),
+                None => args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {}", flag)),
+            };
+
+            match flag {
+                "-h" | "--help" => options.help = true,
+                "-c" | "--catalog" => options.catalog = Some(PathBuf::from(value(flag)?)),
+                "--format" => {
+                    options.format = match value(flag)?.as_str() {
+                        "table" => Format::Table,
+                        "json" => Format::Json,
+                        other => return Err(format!("unknown format {:?}", other)),
+                    }
+                }
+                "--kind" => kinds.push(value(flag)?.parse::<Kind>()?),
+                "--limit" => limit = Some(number(flag, &value(flag)?)?),
+                "--due" | "--days" => {
+                    if due.is_some() {
+                        return Err(String::from("give --due or --days, not both"));
+                    }
+                    let value = value(flag)?;
+                    due = Some(match flag {
+                        "--due" => Due::On(value.parse()?),
+                        _ => Due::InDays(number(flag, &value)?),
+                    });
+                }
+                "--borrower" => borrower = Some(value(flag)?),
+                "--overdue" => overdue = true,
+                "--title" => fields.title = Some(value(flag)?),
+                "--author" => fields.author = Some(value(flag)?),
+                "--director" => fields.director = Some(value(flag)?),
+                "--narrator" => fields.narrator = Some(value(flag)?),
+                "--show" => fields.show = Some(value(flag)?),
+                "--episode" => fields.episode = Some(number(flag, &value(flag)?)?),
+                "--year" => fields.year = Some(number(flag, &value(flag)?)?),
+                "--genre" => fields
+                    .genres
+                    .get_or_insert_with(Vec::new)
+                    .push(value(flag)?),
+                "--tag" => fields.tags.get_or_insert_with(Vec::new).push(value(flag)?),
+                "--rating" => fields.rating = Some(value(flag)?.parse::<Rating>()?),
+                "--minutes" | "--pages" => {
+                    if fields.length.is_some() {
+                        return Err(String::from("give --minutes or --pages, not both"));
+                    }
+                    let count = number(flag, &value(flag)?)?;
+                    fields.length = Some(match flag {
+                        "--minutes" => Length::Minutes(count),
+                        _ => Length::Pages(count),
+                    });
+                }
+                "-" => positional.push(arg.clone()),
+                _ if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let Some((name, rest)) = positional.split_first() else {
+            return Ok(options);
+        };
+        let takes_fields = matches!(name.as_str(), "add" | "edit");
+        let takes_kinds = matches!(name.as_str(), "list" | "search");
+        if !takes_fields && !fields.is_empty() {
+            return Err(format!("{} does not take item fields", name));
+        }
+        if !takes_kinds && !kinds.is_empty() {
+            return Err(format!("{} does not take --kind", name));
+        }
+        if name != "search" && limit.is_some() {
+            return Err(format!("{} does not take --limit", name));
+        }
+        if name != "checkout" && due.is_some() {
+            return Err(format!("{} does not take --due or --days", name));
+        }
+        if name != "loans" && (borrower.is_some() || overdue) {
+            return Err(format!("{} does not take --borrower or --overdue", name));
+        }
+
+        let command = match (name.as_str(), rest) {
+            ("add", []) => Command::Add { kind: None, fields },
+            ("add", [kind]) => Command::Add {
+                kind: Some(kind.parse()?),
+                fields,
+            },
+            ("list", []) => Command::List { kinds },
+            ("show", [id]) => Command::Show { id: id.parse()? },
+            ("search", words) if !words.is_empty() => Command::Search {
+                words: words.to_vec(),
+                kinds,
+                limit,
+            },
+            ("edit", [id]) => Command::Edit {
+                id: id.parse()?,
+                fields,
+            },
+            ("remove", [id]) => Command::Remove { id: id.parse()? },
+            ("import", [file]) => Command::Import {
+                file: PathBuf::from(file),
+            },
+            ("export", []) => Command::Export { file: None },
+            ("export", [file]) => Command::Export {
+                file: Some(PathBuf::from(file)),
+            },
+            ("checkout", [id, borrower]) => Command::CheckOut {
+                id: id.parse()?,
+                borrower: borrower.clone(),
+                due: due.unwrap_or_default(),
+            },
+            ("checkin", [id]) => Command::CheckIn { id: id.parse()? },
+            ("loans", []) => Command::Loans { borrower, overdue },
+            (
+                "add" | "list" | "show" | "search" | "edit" | "remove" | "import" | "export"
+                | "checkout" | "checkin" | "loans",
+                _,
+            ) => return Err(format!("wrong arguments for {}", name)),
+            _ => return Err(format!("unknown command {}", name)),
+        };
+        options.command = Some(command);
+        Ok(options)
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for {}", value, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Options, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn takes_options_before_and_after_the_command() {
+        for line in [
+            "--format json -c shelf.json search dune --kind book --limit 3",
+            "search --limit=3 dune --catalog=shelf.json --kind=book --format=json",
+        ] {
+            let options = parse(line).unwrap();
+            assert_eq!(
+                options.catalog,
+                Some(PathBuf::from("shelf.json")),
+                "{}",
+                line
+            );
+            assert_eq!(options.format, Format::Json, "{}", line);
+            match options.command {
+                Some(Command::Search {
+                    words,
+                    kinds,
+                    limit,
+                }) => {
+                    assert_eq!(words, ["dune"]);
+                    assert_eq!(kinds, [Kind::Book]);
+                    assert_eq!(limit, Some(3));
+                }
+                other => panic!("{}: {:?}", line, other),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_fields_and_due_dates() {
+        let options =
+            parse("add book --title=Dune --genre scifi --genre=classic --pages 412").unwrap();
+        let Some(Command::Add { kind, fields }) = options.command else {
+            panic!("{:?}", options.command);
+        };
+        assert_eq!(kind, Some(Kind::Book));
+        assert_eq!(fields.title.as_deref(), Some("Dune"));
+        assert_eq!(
+            fields.genres,
+            Some(vec![String::from("scifi"), String::from("classic")])
+        );
+        assert_eq!(fields.length, Some(Length::Pages(412)));
+
+        let options = parse("checkout 3 ada --days=7").unwrap();
+        let Some(Command::CheckOut { id, borrower, due }) = options.command else {
+            panic!("{:?}", options.command);
+        };
+        assert_eq!(id.to_string(), "3");
+        assert_eq!(borrower, "ada");
+        assert_eq!(due, Due::InDays(7));
+
+        let options = parse("checkout 3 ada").unwrap();
+        assert!(matches!(
+            options.command,
+            Some(Command::CheckOut {
+                due: Due::InDays(14),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_options_the_command_does_not_take() {
+        for (line, error) in [
+            ("list --title Dune", "list does not take item fields"),
+            ("show 1 --kind book", "show does not take --kind"),
+            ("list --limit 3", "list does not take --limit"),
+            (
+                "list --due 2026-01-01",
+                "list does not take --due or --days",
+            ),
+            (
+                "show 1 --overdue",
+                "show does not take --borrower or --overdue",
+            ),
+            (
+                "checkout 1 ada --due 2026-01-01 --days 3",
+                "give --due or --days, not both",
+            ),
+            (
+                "add book --minutes 90 --pages 3",
+                "give --minutes or --pages, not both",
+            ),
+            (
+                "search dune --limit many",
+                "invalid value \"many\" for --limit",
+            ),
+            ("list --format xml", "unknown format \"xml\""),
+            ("list --verbose", "unknown option --verbose"),
+            ("show", "wrong arguments for show"),
+            ("shelve 1", "unknown command shelve"),
+            ("show --catalog", "missing value for --catalog"),
+        ] {
+            assert_eq!(parse(line).unwrap_err(), error, "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_help_without_a_command() {
+        let options = parse("--help").unwrap();
+        assert!(options.help);
+        assert!(options.command.is_none());
+    }
+}
@@ -1,30 +1,324 @@
-use super::media::{print_media, Media};
+use super::loans::{Loan, Loans};
+#[cfg(test)]
+use super::media::Media;
+use super::media::{Item, Kind};
+use super::metadata::Timestamp;
+use super::search::{Query, SearchResult};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// Ids are never reused, so one stays valid (or reports NotFound) after other items are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Id(u64);
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Id {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Id).map_err(|_| format!("invalid id {:?}", s))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogError {
+    NotFound(Id),
+    Invalid(String),
+    NotLendable(Id, Kind),
+    OnLoan(Id, String),
+    NotOnLoan(Id),
+    NoBorrower,
+    DueBeforeCheckout,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CatalogError::NotFound(id) => write!(f, "no item with id {}", id),
+            CatalogError::Invalid(message) => write!(f, "{}", message),
+            CatalogError::NotLendable(id, kind) => {
+                write!(f, "item {} ({}) cannot be loaned; only books can", id, kind)
+            }
+            CatalogError::OnLoan(id, borrower) => {
+                write!(f, "item {} is on loan to {}", id, borrower)
+            }
+            CatalogError::NotOnLoan(id) => write!(f, "item {} is not on loan", id),
+            CatalogError::NoBorrower => write!(f, "a loan needs a borrower name"),
+            CatalogError::DueBeforeCheckout => write!(f, "the due date has already passed"),
+        }
+    }
+}
+
+impl Error for CatalogError {}
 
 #[derive(Debug)]
 pub struct Catalog {
-    items: Vec<Media>,
+    // Ids only grow, so iterating the map yields items in the order they were added.
+    items: BTreeMap<Id, Item>,
+    next_id: u64,
+    loans: Loans,
 }
 
 impl Catalog {
     pub fn new() -> Catalog {
-        Catalog { items: Vec::new() }
+        Catalog {
+            items: BTreeMap::new(),
+            next_id: 1,
+            loans: Loans::default(),
+        }
+    }
+
+    // Rebuilds a saved catalog; `next_id` is raised past every id so none is handed out twice.
+    pub(super) fn from_items(items: BTreeMap<Id, Item>, next_id: u64, loans: Loans) -> Catalog {
+        let after_last = items.keys().next_back().map_or(1, |id| id.0 + 1);
+        Catalog {
+            items,
+            next_id: next_id.max(after_last),
+            loans,
+        }
+    }
+
+    pub(super) fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    #[cfg(test)]
+    pub fn add(&mut self, media: Media) -> Result<Id, CatalogError> {
+        self.add_item(Item::new(media))
+    }
+
+    // Adds an item with its metadata as given, timestamps included.
+    pub fn add_item(&mut self, item: Item) -> Result<Id, CatalogError> {
+        item.media.validate().map_err(CatalogError::Invalid)?;
+        let id = Id(self.next_id);
+        self.next_id += 1;
+        self.items.insert(id, item);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: Id) -> Result<&Item, CatalogError> {
+        self.items.get(&id).ok_or(CatalogError::NotFound(id))
+    }
+
+    // Applies `change` and marks the item as updated now. A change that leaves the item
+    // invalid is not kept.
+    pub fn update(&mut self, id: Id, change: impl FnOnce(&mut Item)) -> Result<(), CatalogError> {
+        let item = self.items.get_mut(&id).ok_or(CatalogError::NotFound(id))?;
+        let mut changed = item.clone();
+        change(&mut changed);
+        changed.media.validate().map_err(CatalogError::Invalid)?;
+        changed.metadata.updated = Timestamp::now();
+        *item = changed;
+        Ok(())
+    }
+
+    // Items on loan have to be checked in first. Their past loans stay in the history.
+    pub fn remove(&mut self, id: Id) -> Result<Item, CatalogError> {
+        if let Some(loan) = self.loans.current(id) {
+            return Err(CatalogError::OnLoan(id, loan.borrower.clone()));
+        }
+        self.items.remove(&id).ok_or(CatalogError::NotFound(id))
     }
 
-    pub fn add(&mut self, item: Media) {
-        self.items.push(item);
+    pub fn loans(&self) -> &Loans {
+        &self.loans
     }
 
-    pub fn display(&self) {
-        for item in &self.items {
-            print_media(item.clone());
+    // Lends a book from now until `due`.
+    pub fn check_out(
+        &mut self,
+        id: Id,
+        borrower: &str,
+        due: Timestamp,
+    ) -> Result<&Loan, CatalogError> {
+        let kind = self.get(id)?.media.kind();
+        if kind != Kind::Book {
+            return Err(CatalogError::NotLendable(id, kind));
         }
+        if let Some(loan) = self.loans.current(id) {
+            return Err(CatalogError::OnLoan(id, loan.borrower.clone()));
+        }
+        let borrower = borrower.trim();
+        if borrower.is_empty() {
+            return Err(CatalogError::NoBorrower);
+        }
+        let now = Timestamp::now();
+        if due <= now {
+            return Err(CatalogError::DueBeforeCheckout);
+        }
+        Ok(self.loans.push(Loan {
+            item: id,
+            borrower: borrower.to_string(),
+            checked_out: now,
+            due,
+            returned: None,
+        }))
+    }
+
+    pub fn check_in(&mut self, id: Id) -> Result<&Loan, CatalogError> {
+        self.get(id)?;
+        self.loans
+            .close(id, Timestamp::now())
+            .ok_or(CatalogError::NotOnLoan(id))
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &Item)> {
+        self.items.iter().map(|(id, item)| (*id, item))
+    }
+
+    // Most relevant first; equally relevant items keep the order they were added in.
+    pub fn search(&self, query: &Query) -> Vec<SearchResult<'_>> {
+        let mut results: Vec<SearchResult> = self
+            .iter()
+            .filter_map(|(id, item)| {
+                let score = query.score(&item.media)?;
+                Some(SearchResult { id, item, score })
+            })
+            .collect();
+        results.sort_by_key(|result| Reverse(result.score));
+        if let Some(limit) = query.max_results() {
+            results.truncate(limit);
+        }
+        results
     }
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Catalog::new()
+    }
+}
 
-    pub fn get_by_index(&self, index: usize) -> Option<&Media> {
-        if index < self.items.len() {
-            Some(&self.items[index])
-        } else {
-            None
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(title: &str) -> Media {
+        Media::Book {
+            title: title.to_string(),
+            author: String::from("Someone"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn ids_stay_stable_across_removals() {
+        let mut catalog = Catalog::new();
+        let first = catalog.add(book("First")).unwrap();
+        let second = catalog.add(book("Second")).unwrap();
+        let third = catalog.add(book("Third")).unwrap();
+
+        catalog.remove(second).unwrap();
+        assert_eq!(
+            catalog.get(second).unwrap_err(),
+            CatalogError::NotFound(second)
+        );
+        assert_eq!(catalog.get(third).unwrap().media.title(), Some("Third"));
+        assert_eq!(
+            catalog.remove(second).unwrap_err(),
+            CatalogError::NotFound(second)
+        );
+
+        // Removing the newest item does not free its id either.
+        catalog.remove(third).unwrap();
+        let fourth = catalog.add(book("Fourth")).unwrap();
+        assert!(fourth > third);
+        let ids: Vec<Id> = catalog.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![first, fourth]);
+    }
+
+    #[test]
+    fn rejects_invalid_items() {
+        let mut catalog = Catalog::new();
+        assert_eq!(
+            catalog.add(book("")).unwrap_err(),
+            CatalogError::Invalid(String::from("title cannot be empty"))
+        );
+        assert!(catalog.is_empty());
+
+        let id = catalog.add(book("Dune")).unwrap();
+        let result = catalog.update(id, |item| {
+            item.media = Media::Movie {
+                title: String::from("Dune"),
+                director: String::new(),
+            }
+        });
+        assert_eq!(
+            result.unwrap_err(),
+            CatalogError::Invalid(String::from("director cannot be empty"))
+        );
+        assert_eq!(catalog.get(id).unwrap().media.kind(), Kind::Book);
+    }
+
+    #[test]
+    fn lends_a_book_to_one_borrower_at_a_time() {
+        let mut catalog = Catalog::new();
+        let id = catalog.add(book("Dune")).unwrap();
+        let due = Timestamp::now().plus_days(14).unwrap();
+
+        assert_eq!(catalog.check_out(id, " Ada ", due).unwrap().borrower, "Ada");
+        assert_eq!(
+            catalog.check_out(id, "Bob", due).unwrap_err(),
+            CatalogError::OnLoan(id, String::from("Ada"))
+        );
+        assert_eq!(
+            catalog.remove(id).unwrap_err(),
+            CatalogError::OnLoan(id, String::from("Ada"))
+        );
+
+        assert!(catalog.check_in(id).unwrap().returned.is_some());
+        assert_eq!(
+            catalog.check_in(id).unwrap_err(),
+            CatalogError::NotOnLoan(id)
+        );
+        catalog.check_out(id, "Bob", due).unwrap();
+        catalog.check_in(id).unwrap();
+        catalog.remove(id).unwrap();
+        assert_eq!(catalog.loans().history("ada").len(), 1);
+        assert_eq!(catalog.loans().len(), 2);
+    }
+
+    #[test]
+    fn refuses_invalid_loans() {
+        let mut catalog = Catalog::new();
+        let id = catalog.add(book("Dune")).unwrap();
+        let movie = catalog
+            .add(Media::Movie {
+                title: String::from("Alien"),
+                director: String::from("Ridley Scott"),
+            })
+            .unwrap();
+        let due = Timestamp::now().plus_days(14).unwrap();
+
+        let error = catalog.check_out(movie, "Ada", due).unwrap_err();
+        assert_eq!(error, CatalogError::NotLendable(movie, Kind::Movie));
+        assert_eq!(
+            error.to_string(),
+            "item 2 (Movie) cannot be loaned; only books can"
+        );
+        assert_eq!(
+            catalog.check_out(id, "  ", due).unwrap_err(),
+            CatalogError::NoBorrower
+        );
+        assert_eq!(
+            catalog
+                .check_out(id, "Ada", Timestamp::from_secs(0))
+                .unwrap_err(),
+            CatalogError::DueBeforeCheckout
+        );
+        assert!(catalog.loans().is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use super::catalog::Id;
+use super::metadata::Timestamp;
+use serde::{Deserialize, Serialize};
+
+// One checkout of an item; `returned` is set when it comes back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Loan {
+    pub item: Id,
+    pub borrower: String,
+    pub checked_out: Timestamp,
+    pub due: Timestamp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returned: Option<Timestamp>,
+}
+
+impl Loan {
+    pub fn is_open(&self) -> bool {
+        self.returned.is_none()
+    }
+
+    pub fn is_overdue(&self, now: Timestamp) -> bool {
+        self.is_open() && now > self.due
+    }
+}
+
+// Every loan ever made, oldest first. Returned loans stay as history; an item has at most
+// one open loan at a time.
+#[derive(Debug, Clone, Default)]
+pub struct Loans {
+    records: Vec<Loan>,
+}
+
+impl Loans {
+    pub(super) fn from_records(records: Vec<Loan>) -> Loans {
+        Loans { records }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Loan> {
+        self.records.iter()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // The open loan of an item, if it is out.
+    pub fn current(&self, item: Id) -> Option<&Loan> {
+        self.records
+            .iter()
+            .rev()
+            .find(|loan| loan.item == item && loan.is_open())
+    }
+
+    pub fn open(&self) -> impl Iterator<Item = &Loan> {
+        self.records.iter().filter(|loan| loan.is_open())
+    }
+
+    // Longest overdue first.
+    pub fn overdue(&self, now: Timestamp) -> Vec<&Loan> {
+        let mut overdue: Vec<&Loan> = self
+            .records
+            .iter()
+            .filter(|loan| loan.is_overdue(now))
+            .collect();
+        overdue.sort_by_key(|loan| loan.due);
+        overdue
+    }
+
+    // Everything a borrower has had, oldest first. Names match case-insensitively.
+    pub fn history(&self, borrower: &str) -> Vec<&Loan> {
+        let borrower = borrower.trim().to_lowercase();
+        self.records
+            .iter()
+            .filter(|loan| loan.borrower.to_lowercase() == borrower)
+            .collect()
+    }
+
+    pub(super) fn push(&mut self, loan: Loan) -> &Loan {
+        self.records.push(loan);
+        &self.records[self.records.len() - 1]
+    }
+
+    pub(super) fn close(&mut self, item: Id, returned: Timestamp) -> Option<&Loan> {
+        let loan = self
+            .records
+            .iter_mut()
+            .rev()
+            .find(|loan| loan.item == item && loan.is_open())?;
+        loan.returned = Some(returned);
+        Some(loan)
+    }
+}
@@ -1,14 +1,150 @@
+use super::metadata::Metadata;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum Media {
-    Book { title: String, author: String },
-    Movie { title: String, director: String },
-    Audiobook { title: String },
-    Podcast { episode: u32 },
+    Book {
+        title: String,
+        author: String,
+    },
+    Movie {
+        title: String,
+        director: String,
+    },
+    Audiobook {
+        title: String,
+        // Defaults keep catalogs saved before these fields existed loadable.
+        #[serde(default)]
+        author: String,
+        #[serde(default)]
+        narrator: String,
+    },
+    Podcast {
+        #[serde(default)]
+        show: String,
+        episode: u32,
+        #[serde(default)]
+        title: String,
+    },
     Placeholder,
 }
 
+// A catalog entry: what the item is, plus the details shared by every kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    #[serde(flatten)]
+    pub media: Media,
+    #[serde(flatten)]
+    pub metadata: Metadata,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Book,
+    Movie,
+    Audiobook,
+    Podcast,
+    Placeholder,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [
+        Kind::Book,
+        Kind::Movie,
+        Kind::Audiobook,
+        Kind::Podcast,
+        Kind::Placeholder,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Book => "Book",
+            Kind::Movie => "Movie",
+            Kind::Audiobook => "Audiobook",
+            Kind::Podcast => "Podcast",
+            Kind::Placeholder => "Placeholder",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// Case-insensitive, so "book" and "Book" both work.
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Kind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown kind {:?}", s))
+    }
+}
+
 impl Media {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Media::Book { .. } => Kind::Book,
+            Media::Movie { .. } => Kind::Movie,
+            Media::Audiobook { .. } => Kind::Audiobook,
+            Media::Podcast { .. } => Kind::Podcast,
+            Media::Placeholder => Kind::Placeholder,
+        }
+    }
+
+    // Titles are required except for podcasts, as are a book's author and a movie's director.
+    pub fn validate(&self) -> Result<(), String> {
+        let missing = match self {
+            Media::Book { title, .. }
+            | Media::Movie { title, .. }
+            | Media::Audiobook { title, .. }
+                if title.is_empty() =>
+            {
+                Some("title")
+            }
+            Media::Book { author, .. } if author.is_empty() => Some("author"),
+            Media::Movie { director, .. } if director.is_empty() => Some("director"),
+            _ => None,
+        };
+        match missing {
+            Some(field) => Err(format!("{} cannot be empty", field)),
+            None => Ok(()),
+        }
+    }
+
+    // The episode title for podcasts.
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Media::Book { title, .. }
+            | Media::Movie { title, .. }
+            | Media::Audiobook { title, .. }
+            | Media::Podcast { title, .. } => {
+                Some(title.as_str()).filter(|title| !title.is_empty())
+            }
+            Media::Placeholder => None,
+        }
+    }
+
+    // Authors, directors and narrators.
+    pub fn people(&self) -> Vec<&str> {
+        let people = match self {
+            Media::Book { author, .. } => vec![author.as_str()],
+            Media::Movie { director, .. } => vec![director.as_str()],
+            Media::Audiobook {
+                author, narrator, ..
+            } => vec![author.as_str(), narrator.as_str()],
+            Media::Podcast { .. } | Media::Placeholder => Vec::new(),
+        };
+        people.into_iter().filter(|name| !name.is_empty()).collect()
+    }
+
     pub fn description(&self) -> String {
         match self {
             Media::Book { title, author } => {
@@ -17,35 +153,71 @@ impl Media {
             Media::Movie { title, director } => {
                 format!("Movie: {} by {}", title, director)
             }
-            Media::Audiobook { title } => {
-                format!("Audiobook: {}", title)
+            Media::Audiobook {
+                title,
+                author,
+                narrator,
+            } => {
+                let mut description = format!("Audiobook: {}", title);
+                if !author.is_empty() {
+                    description.push_str(&format!(" by {}", author));
+                }
+                if !narrator.is_empty() {
+                    description.push_str(&format!(", read by {}", narrator));
+                }
+                description
             }
-            Media::Podcast { episode } => {
-                format!("Podcast: Episode {}", episode)
-            }
-            Media::Placeholder => {
-                format!("Placeholder")
+            Media::Podcast {
+                show,
+                episode,
+                title,
+            } => {
+                let mut description = String::from("Podcast: ");
+                if !show.is_empty() {
+                    description.push_str(&format!("{}, ", show));
+                }
+                description.push_str(&format!("Episode {}", episode));
+                if !title.is_empty() {
+                    description.push_str(&format!(": {}", title));
+                }
+                description
             }
+            Media::Placeholder => "Placeholder".to_string(),
         }
     }
 }
 
-pub fn print_media(media: Media) {
-    match media {
-        Media::Book { title, author } => {
-            println!("Book: {} by {}", title, author);
+impl Item {
+    pub fn new(media: Media) -> Item {
+        Item {
+            media,
+            metadata: Metadata::new(),
+        }
+    }
+
+    // The media's description followed by year, length, genres and rating when known, e.g.
+    // "Book: Dune by Frank Herbert (1965, 412 pages, science fiction, rated 5/5)".
+    pub fn description(&self) -> String {
+        let metadata = &self.metadata;
+        let mut details = Vec::new();
+        if let Some(year) = metadata.year {
+            details.push(year.to_string());
         }
-        Media::Movie { title, director } => {
-            println!("Movie: {} by {}", title, director);
+        if let Some(length) = metadata.length {
+            details.push(length.to_string());
         }
-        Media::Audiobook { title } => {
-            println!("Audiobook: {}", title);
+        if !metadata.genres.is_empty() {
+            details.push(metadata.genres.join("/"));
         }
-        Media::Podcast { episode } => {
-            println!("Podcast: Episode {}", episode);
+        if let Some(rating) = metadata.rating {
+            details.push(format!("rated {}", rating));
         }
-        Media::Placeholder => {
-            println!("Placeholder");
+
+        let description = self.media.description();
+        if details.is_empty() {
+            description
+        } else {
+            format!("{} ({})", description, details.join(", "))
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Details every item can carry, whatever its kind. Only the timestamps are always set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Rating>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<Length>,
+    // Files from before timestamps existed get the time they were loaded.
+    #[serde(default = "Timestamp::now")]
+    pub added: Timestamp,
+    #[serde(default = "Timestamp::now")]
+    pub updated: Timestamp,
+}
+
+impl Metadata {
+    pub fn new() -> Metadata {
+        let now = Timestamp::now();
+        Metadata {
+            year: None,
+            genres: Vec::new(),
+            tags: Vec::new(),
+            rating: None,
+            length: None,
+            added: now,
+            updated: now,
+        }
+    }
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata::new()
+    }
+}
+
+// A user rating from 1 to 5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Rating(u8);
+
+impl Rating {
+    pub fn new(stars: u8) -> Result<Rating, String> {
+        if (1..=5).contains(&stars) {
+            Ok(Rating(stars))
+        } else {
+            Err(format!("rating must be from 1 to 5, not {}", stars))
+        }
+    }
+
+    pub fn stars(&self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for Rating {
+    type Error = String;
+
+    fn try_from(stars: u8) -> Result<Self, Self::Error> {
+        Rating::new(stars)
+    }
+}
+
+impl From<Rating> for u8 {
+    fn from(rating: Rating) -> u8 {
+        rating.0
+    }
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/5", self.0)
+    }
+}
+
+impl FromStr for Rating {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stars = s.parse().map_err(|_| format!("invalid rating {:?}", s))?;
+        Rating::new(stars)
+    }
+}
+
+// Running time for things you watch or listen to, page count for things you read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Length {
+    Minutes(u32),
+    Pages(u32),
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Length::Minutes(minutes) => write!(f, "{} min", minutes),
+            Length::Pages(pages) => write!(f, "{} pages", pages),
+        }
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 86_400;
+// 9999-12-31T23:59:59Z, the last second of the last year dates are accepted for.
+const LAST_SECOND: u64 = 253_402_300_799;
+
+// Whole seconds since the Unix epoch, shown in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Timestamp(since_epoch.as_secs())
+    }
+
+    #[cfg(test)]
+    pub fn from_secs(seconds: u64) -> Timestamp {
+        Timestamp(seconds)
+    }
+
+    // Midnight UTC at the start of the given day; None for impossible dates and years
+    // outside 1970 to 9999.
+    pub fn from_date(year: i64, month: i64, day: i64) -> Option<Timestamp> {
+        if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day)
+        {
+            return None;
+        }
+        let days = days_from_civil(year, month, day);
+        // Catches days past the end of the month, e.g. February 30th.
+        if civil_from_days(days) != (year, month, day) {
+            return None;
+        }
+        Some(Timestamp(days as u64 * SECONDS_PER_DAY))
+    }
+
+    // The last second of the UTC day this falls in.
+    pub fn end_of_day(&self) -> Timestamp {
+        Timestamp(self.0 - self.0 % SECONDS_PER_DAY + SECONDS_PER_DAY - 1)
+    }
+
+    // None past the end of year 9999, like `from_date`.
+    pub fn plus_days(&self, days: u32) -> Option<Timestamp> {
+        u64::from(days)
+            .checked_mul(SECONDS_PER_DAY)
+            .and_then(|seconds| self.0.checked_add(seconds))
+            .filter(|seconds| *seconds <= LAST_SECOND)
+            .map(Timestamp)
+    }
+
+    // The UTC date alone, e.g. "2024-05-01".
+    pub fn date(&self) -> String {
+        let (year, month, day) = civil_from_days((self.0 / SECONDS_PER_DAY) as i64);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+// RFC 3339, e.g. "2024-05-01T12:30:00Z".
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.0 % SECONDS_PER_DAY;
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date(),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+// Accepts a date such as "2024-05-01", meaning midnight UTC at its start.
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date {:?}, expected YYYY-MM-DD", s);
+        let mut parts = s.splitn(3, '-');
+        let mut part = || -> Result<i64, String> {
+            let part = parts.next().ok_or_else(invalid)?;
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse().map_err(|_| invalid())
+        };
+        let (year, month, day) = (part()?, part()?, part()?);
+        Timestamp::from_date(year, month, day).ok_or_else(invalid)
+    }
+}
+
+// Howard Hinnant's civil-from-days algorithm: days since the epoch to (year, month, day).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// The inverse, from the same source.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_dates() {
+        assert_eq!(Timestamp::from_date(1970, 1, 1), Some(Timestamp(0)));
+        let leap_day: Timestamp = "2024-02-29".parse().unwrap();
+        assert_eq!(leap_day.to_string(), "2024-02-29T00:00:00Z");
+        assert_eq!(leap_day.end_of_day().to_string(), "2024-02-29T23:59:59Z");
+        assert_eq!(leap_day.plus_days(1).unwrap().date(), "2024-03-01");
+        assert_eq!(
+            "2000-02-29".parse::<Timestamp>().unwrap().date(),
+            "2000-02-29"
+        );
+
+        for date in [
+            "2023-02-29",
+            "2024-02-30",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-13-01",
+        ] {
+            assert!(date.parse::<Timestamp>().is_err(), "{}", date);
+        }
+        for date in ["2024-5-1x", "2024-05", "-2024-05-01", "2024-05-01-"] {
+            assert!(date.parse::<Timestamp>().is_err(), "{}", date);
+        }
+    }
+
+    #[test]
+    fn bounds_dates_to_years_1970_to_9999() {
+        assert_eq!(Timestamp::from_date(1969, 12, 31), None);
+        assert_eq!(Timestamp::from_date(10_000, 1, 1), None);
+        assert_eq!(Timestamp::from_date(i64::MAX, 1, 1), None);
+        assert!("99999999999999-01-01".parse::<Timestamp>().is_err());
+
+        let last_day = Timestamp::from_date(9999, 12, 31).unwrap();
+        assert_eq!(last_day.end_of_day(), Timestamp(LAST_SECOND));
+        assert_eq!(last_day.plus_days(1), None);
+        assert_eq!(Timestamp(0).plus_days(u32::MAX), None);
+        assert_eq!(Timestamp(u64::MAX).plus_days(1), None);
+    }
+}
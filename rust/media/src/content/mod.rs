@@ -1,2 +1,6 @@
+pub mod catalog;
+pub mod loans;
 pub mod media;
-pub mod catalog;
\ No newline at end of file
+pub mod metadata;
+pub mod search;
+pub mod storage;
@@ -0,0 +1,191 @@
+use super::catalog::Id;
+use super::media::{Item, Kind, Media};
+
+// Title and show matches count for more than matches on people.
+const TITLE_WEIGHT: u32 = 2;
+const PERSON_WEIGHT: u32 = 1;
+
+// Words in the text are matched case-insensitively against titles, podcast shows and
+// people (authors, directors, narrators); every word has to match somewhere. An empty
+// text matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    text: String,
+    words: Vec<String>,
+    kinds: Vec<Kind>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new(text: &str) -> Query {
+        let text = text.trim().to_lowercase();
+        Query {
+            words: words(&text).map(str::to_string).collect(),
+            text,
+            kinds: Vec::new(),
+            limit: None,
+        }
+    }
+
+    // Only returns items of the given kinds; may be repeated.
+    pub fn kind(mut self, kind: Kind) -> Query {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Query {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub(crate) fn max_results(&self) -> Option<usize> {
+        self.limit
+    }
+
+    // None when the item does not match; otherwise higher is more relevant.
+    pub(crate) fn score(&self, item: &Media) -> Option<u32> {
+        if !self.kinds.is_empty() && !self.kinds.contains(&item.kind()) {
+            return None;
+        }
+        if self.words.is_empty() {
+            return Some(0);
+        }
+
+        let title = item.title().map(str::to_lowercase);
+        let mut fields: Vec<(String, u32)> = Vec::new();
+        if let Some(title) = &title {
+            fields.push((title.clone(), TITLE_WEIGHT));
+        }
+        if let Media::Podcast { show, .. } = item {
+            if !show.is_empty() {
+                fields.push((show.to_lowercase(), TITLE_WEIGHT));
+            }
+        }
+        for person in item.people() {
+            fields.push((person.to_lowercase(), PERSON_WEIGHT));
+        }
+
+        let mut score = 0;
+        for word in &self.words {
+            score += fields
+                .iter()
+                .map(|(field, weight)| word_score(word, field) * weight)
+                .max()
+                .filter(|score| *score > 0)?;
+        }
+        // The whole query appearing in the title beats the same words scattered around.
+        if let Some(title) = &title {
+            if *title == self.text {
+                score += 10;
+            } else if title.contains(&self.text) {
+                score += 3;
+            }
+        }
+        Some(score)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResult<'a> {
+    pub id: Id,
+    pub item: &'a Item,
+    pub score: u32,
+}
+
+// A whole word beats the start of a word, which beats a match anywhere in the field.
+fn word_score(word: &str, field: &str) -> u32 {
+    if words(field).any(|candidate| candidate == word) {
+        3
+    } else if words(field).any(|candidate| candidate.starts_with(word)) {
+        2
+    } else if field.contains(word) {
+        1
+    } else {
+        0
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::catalog::Catalog;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::new();
+        let items = [
+            Media::Book {
+                title: String::from("The Left Hand of Darkness"),
+                author: String::from("Ursula K. Le Guin"),
+            },
+            Media::Book {
+                title: String::from("Darkness at Noon"),
+                author: String::from("Arthur Koestler"),
+            },
+            Media::Movie {
+                title: String::from("Dark City"),
+                director: String::from("Alex Proyas"),
+            },
+            Media::Audiobook {
+                title: String::from("Noon"),
+                author: String::from("Aatish Taseer"),
+                narrator: String::from("Darkness Reader"),
+            },
+        ];
+        for media in items {
+            catalog.add(media).unwrap();
+        }
+        catalog
+    }
+
+    fn titles(catalog: &Catalog, query: &Query) -> Vec<String> {
+        catalog
+            .search(query)
+            .iter()
+            .map(|result| result.item.media.title().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_title_matches_above_people() {
+        let catalog = catalog();
+        // A whole word in the title beats the start of one, which beats a narrator match;
+        // the two equal scores keep the order their items were added in.
+        assert_eq!(
+            titles(&catalog, &Query::new("dark")),
+            vec![
+                "Dark City",
+                "The Left Hand of Darkness",
+                "Darkness at Noon",
+                "Noon"
+            ]
+        );
+        // Every word also matches the audiobook somewhere, but the exact title wins.
+        assert_eq!(
+            titles(&catalog, &Query::new("Darkness at Noon")),
+            vec!["Darkness at Noon", "Noon"]
+        );
+        assert!(titles(&catalog, &Query::new("darkness zebra")).is_empty());
+    }
+
+    #[test]
+    fn filters_by_kind_and_limits() {
+        let catalog = catalog();
+        assert_eq!(
+            titles(&catalog, &Query::new("dark").kind(Kind::Movie)),
+            vec!["Dark City"]
+        );
+        assert_eq!(
+            titles(
+                &catalog,
+                &Query::new("").kind(Kind::Movie).kind(Kind::Audiobook)
+            ),
+            vec!["Dark City", "Noon"]
+        );
+        assert_eq!(titles(&catalog, &Query::new("").limit(2)).len(), 2);
+    }
+}
@@ -0,0 +1,585 @@
+use super::catalog::{Catalog, Id};
+use super::loans::{Loan, Loans};
+use super::media::{Item, Kind, Media};
+use super::metadata::Length;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Bumped whenever the JSON layout changes in a way older readers cannot handle.
+// Older versions can still be loaded.
+pub const FORMAT_VERSION: u32 = 3;
+
+const CSV_HEADER: [&str; 16] = [
+    "id", "kind", "title", "author", "director", "narrator", "show", "episode", "year", "genres",
+    "tags", "rating", "minutes", "pages", "added", "updated",
+];
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(PathBuf, io::Error),
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    UnsupportedVersion(u32),
+    DuplicateId(Id),
+    InvalidItem(Id, String),
+    InvalidLoan(Id),
+    MissingColumn(&'static str),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            StorageError::Json(e) => write!(f, "invalid catalog file: {}", e),
+            StorageError::Csv(e) => write!(f, "invalid CSV: {}", e),
+            StorageError::UnsupportedVersion(version) => write!(
+                f,
+                "catalog file version {} is not supported (newest is {})",
+                version, FORMAT_VERSION
+            ),
+            StorageError::DuplicateId(id) => write!(f, "id {} appears more than once", id),
+            StorageError::InvalidItem(id, message) => write!(f, "item {}: {}", id, message),
+            StorageError::InvalidLoan(id) => write!(
+                f,
+                "item {} has an open loan but is missing or already on loan",
+                id
+            ),
+            StorageError::MissingColumn(name) => write!(f, "CSV header has no {:?} column", name),
+        }
+    }
+}
+
+impl Error for StorageError {}
+
+// A CSV line that could not be imported; the rest of the file still is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub added: Vec<Id>,
+    pub errors: Vec<RowError>,
+}
+
+// Keeps other processes that call `Catalog::lock` on the same path waiting until dropped.
+#[derive(Debug)]
+pub struct CatalogLock {
+    _file: File,
+}
+
+#[derive(Serialize)]
+struct SavedCatalog<'a> {
+    version: u32,
+    next_id: u64,
+    items: Vec<SavedItem<'a>>,
+    loans: Vec<&'a Loan>,
+}
+
+#[derive(Serialize)]
+struct SavedItem<'a> {
+    id: Id,
+    #[serde(flatten)]
+    item: &'a Item,
+}
+
+#[derive(Deserialize)]
+struct FileVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct LoadedCatalog {
+    #[serde(default)]
+    next_id: u64,
+    items: Vec<LoadedItem>,
+    #[serde(default)]
+    loans: Vec<Loan>,
+}
+
+#[derive(Deserialize)]
+struct LoadedItem {
+    id: Id,
+    #[serde(flatten)]
+    item: Item,
+}
+
+impl Catalog {
+    // Waits for, then takes, an exclusive lock for a load, change and save of `path`. The
+    // lock lives on "<path>.lock", which is left in place, and is advisory: it only holds
+    // back others that lock too.
+    pub fn lock(path: &Path) -> Result<CatalogLock, StorageError> {
+        let lock_path = sibling(path, ".lock");
+        let io_error = |e| StorageError::Io(lock_path.clone(), e);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(io_error)?;
+        file.lock().map_err(io_error)?;
+        Ok(CatalogLock { _file: file })
+    }
+
+    // Writes every item with its id, plus the next id so removed ids stay retired.
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        let saved = SavedCatalog {
+            version: FORMAT_VERSION,
+            next_id: self.next_id(),
+            items: self
+                .iter()
+                .map(|(id, item)| SavedItem { id, item })
+                .collect(),
+            loans: self.loans().iter().collect(),
+        };
+        write_atomically(path, |writer| {
+            serde_json::to_writer_pretty(&mut *writer, &saved).map_err(StorageError::Json)?;
+            writeln!(writer).map_err(|e| StorageError::Io(path.to_path_buf(), e))
+        })
+    }
+
+    // Version 1 files predate metadata; their items get defaults and load-time timestamps.
+    // Files before version 3 have no loans.
+    pub fn load(path: &Path) -> Result<Catalog, StorageError> {
+        let text = fs::read_to_string(path).map_err(|e| StorageError::Io(path.to_path_buf(), e))?;
+        let FileVersion { version } = serde_json::from_str(&text).map_err(StorageError::Json)?;
+        if !(1..=FORMAT_VERSION).contains(&version) {
+            return Err(StorageError::UnsupportedVersion(version));
+        }
+
+        let loaded: LoadedCatalog = serde_json::from_str(&text).map_err(StorageError::Json)?;
+        let mut items = BTreeMap::new();
+        for loaded_item in loaded.items {
+            // The same rules as adding an item, so a hand-edited file cannot sneak in an
+            // item the commands would refuse.
+            loaded_item
+                .item
+                .media
+                .validate()
+                .map_err(|message| StorageError::InvalidItem(loaded_item.id, message))?;
+            if items.insert(loaded_item.id, loaded_item.item).is_some() {
+                return Err(StorageError::DuplicateId(loaded_item.id));
+            }
+        }
+        let mut lent = BTreeSet::new();
+        for loan in loaded.loans.iter().filter(|loan| loan.is_open()) {
+            if !items.contains_key(&loan.item) || !lent.insert(loan.item) {
+                return Err(StorageError::InvalidLoan(loan.item));
+            }
+        }
+        let loans = Loans::from_records(loaded.loans);
+        Ok(Catalog::from_items(items, loaded.next_id, loans))
+    }
+
+    // One row per item under a header row; columns that do not apply to a kind are empty.
+    // Genres and tags are separated by semicolons.
+    pub fn export_csv(&self, writer: impl Write) -> Result<(), StorageError> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(CSV_HEADER).map_err(StorageError::Csv)?;
+        for (id, item) in self.iter() {
+            writer
+                .write_record(csv_row(id, item))
+                .map_err(StorageError::Csv)?;
+        }
+        writer
+            .flush()
+            .map_err(|e| StorageError::Csv(csv::Error::from(e)))
+    }
+
+    pub fn save_csv(&self, path: &Path) -> Result<(), StorageError> {
+        write_atomically(path, |writer| self.export_csv(writer))
+    }
+
+    // Adds every valid row as a new item; the id, added and updated columns are ignored.
+    // Columns are found by name, so they may come in any order and unknown ones are skipped.
+    pub fn import_csv(&mut self, reader: impl Read) -> Result<ImportReport, StorageError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers = reader.headers().map_err(StorageError::Csv)?.clone();
+        if !headers
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case("kind"))
+        {
+            return Err(StorageError::MissingColumn("kind"));
+        }
+
+        let mut report = ImportReport::default();
+        for record in reader.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(e) if e.is_io_error() => return Err(StorageError::Csv(e)),
+                Err(e) => {
+                    report.errors.push(RowError {
+                        line: e.position().map_or(0, |position| position.line()),
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let field = |name: &str| {
+                headers
+                    .iter()
+                    .position(|header| header.eq_ignore_ascii_case(name))
+                    .and_then(|i| record.get(i))
+                    .unwrap_or("")
+            };
+            let added = item_from_row(field)
+                .and_then(|item| self.add_item(item).map_err(|e| e.to_string()));
+            match added {
+                Ok(id) => report.added.push(id),
+                Err(message) => report.errors.push(RowError {
+                    line: record.position().map_or(0, |position| position.line()),
+                    message,
+                }),
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn csv_row(id: Id, item: &Item) -> Vec<String> {
+    let (mut author, mut director, mut narrator, mut show, mut episode) = (
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+    );
+    match &item.media {
+        Media::Book { author: name, .. } => author = name.clone(),
+        Media::Movie { director: name, .. } => director = name.clone(),
+        Media::Audiobook {
+            author: name,
+            narrator: reader,
+            ..
+        } => {
+            author = name.clone();
+            narrator = reader.clone();
+        }
+        Media::Podcast {
+            show: name,
+            episode: number,
+            ..
+        } => {
+            show = name.clone();
+            episode = number.to_string();
+        }
+        Media::Placeholder => {}
+    }
+    let metadata = &item.metadata;
+    let (minutes, pages) = match metadata.length {
+        Some(Length::Minutes(minutes)) => (minutes.to_string(), String::new()),
+        Some(Length::Pages(pages)) => (String::new(), pages.to_string()),
+        None => (String::new(), String::new()),
+    };
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    vec![
+        id.to_string(),
+        item.media.kind().name().to_string(),
+        item.media.title().unwrap_or("").to_string(),
+        author,
+        director,
+        narrator,
+        show,
+        episode,
+        optional(metadata.year.map(|year| year.to_string())),
+        metadata.genres.join(";"),
+        metadata.tags.join(";"),
+        optional(metadata.rating.map(|rating| rating.stars().to_string())),
+        minutes,
+        pages,
+        metadata.added.to_string(),
+        metadata.updated.to_string(),
+    ]
+}
+
+fn item_from_row<'a>(field: impl Fn(&str) -> &'a str) -> Result<Item, String> {
+    let number = |name: &str| -> Result<Option<u32>, String> {
+        match field(name) {
+            "" => Ok(None),
+            value => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid {} {:?}", name, value)),
+        }
+    };
+    let list = |name: &str| -> Vec<String> {
+        field(name)
+            .split(';')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    let media = match field("kind").parse::<Kind>()? {
+        Kind::Book => Media::Book {
+            title: field("title").to_string(),
+            author: field("author").to_string(),
+        },
+        Kind::Movie => Media::Movie {
+            title: field("title").to_string(),
+            director: field("director").to_string(),
+        },
+        Kind::Audiobook => Media::Audiobook {
+            title: field("title").to_string(),
+            author: field("author").to_string(),
+            narrator: field("narrator").to_string(),
+        },
+        Kind::Podcast => Media::Podcast {
+            show: field("show").to_string(),
+            episode: number("episode")?.ok_or("missing episode")?,
+            title: field("title").to_string(),
+        },
+        Kind::Placeholder => Media::Placeholder,
+    };
+
+    let mut item = Item::new(media);
+    let metadata = &mut item.metadata;
+    metadata.year = match field("year") {
+        "" => None,
+        year => Some(
+            year.parse()
+                .map_err(|_| format!("invalid year {:?}", year))?,
+        ),
+    };
+    metadata.genres = list("genres");
+    metadata.tags = list("tags");
+    metadata.rating = match field("rating") {
+        "" => None,
+        rating => Some(rating.parse()?),
+    };
+    metadata.length = match (number("minutes")?, number("pages")?) {
+        (Some(_), Some(_)) => return Err(String::from("give minutes or pages, not both")),
+        (Some(minutes), None) => Some(Length::Minutes(minutes)),
+        (None, Some(pages)) => Some(Length::Pages(pages)),
+        (None, None) => None,
+    };
+    Ok(item)
+}
+
+// Writes to a temporary file next to `path` and renames it over, so a crash leaves either
+// the old file or the new one, never a partial write. Each call gets its own temporary file,
+// so concurrent saves do not clobber each other, but the last rename wins: callers that load,
+// change and save must hold `Catalog::lock` to avoid losing updates.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), StorageError>,
+) -> Result<(), StorageError> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let io_error = |e| StorageError::Io(path.to_path_buf(), e);
+    let (file, temporary) = loop {
+        let suffix = format!(
+            ".{}.{}.tmp",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let temporary = sibling(path, &suffix);
+        // A leftover from an earlier process with the same pid is skipped, not overwritten.
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temporary)
+        {
+            Ok(file) => break (file, temporary),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(io_error(e)),
+        }
+    };
+
+    let mut writer = BufWriter::new(file);
+    let result = write(&mut writer).and_then(|()| {
+        let file = writer.into_inner().map_err(|e| io_error(e.into_error()))?;
+        file.sync_all().map_err(io_error)?;
+        fs::rename(&temporary, path).map_err(io_error)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+        return result;
+    }
+    sync_parent(path).map_err(io_error)
+}
+
+// `path` with `suffix` appended to its file name, so the result is in the same directory.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Makes the rename itself durable. Directories cannot be opened as files on Windows, where
+// the rename is already durable once it returns.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::metadata::{Rating, Timestamp};
+
+    // A directory of its own, so tests running in parallel do not share files; removed when
+    // dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("media-{}-{}", process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn catalog(&self) -> PathBuf {
+            self.0.join("catalog.json")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn load_text(name: &str, text: &str) -> Result<Catalog, StorageError> {
+        let dir = TempDir::new(name);
+        let path = dir.catalog();
+        fs::write(&path, text).unwrap();
+        Catalog::load(&path)
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut catalog = Catalog::new();
+        let mut item = Item::new(Media::Book {
+            title: String::from("Dune"),
+            author: String::from("Frank Herbert"),
+        });
+        item.metadata.year = Some(1965);
+        item.metadata.genres = vec![String::from("science fiction")];
+        item.metadata.rating = Some(Rating::new(5).unwrap());
+        item.metadata.length = Some(Length::Pages(412));
+        let dune = catalog.add_item(item).unwrap();
+        let removed = catalog.add(Media::Placeholder).unwrap();
+        catalog.remove(removed).unwrap();
+        let due = Timestamp::now().plus_days(14).unwrap();
+        catalog.check_out(dune, "Ada", due).unwrap();
+
+        let dir = TempDir::new("round-trip");
+        let path = dir.catalog();
+        catalog.save(&path).unwrap();
+        let mut loaded = Catalog::load(&path).unwrap();
+        assert_eq!(
+            loaded.get(dune).unwrap().description(),
+            catalog.get(dune).unwrap().description()
+        );
+        assert_eq!(
+            loaded.get(dune).unwrap().metadata,
+            catalog.get(dune).unwrap().metadata
+        );
+        assert_eq!(loaded.loans().current(dune).unwrap().due, due);
+        // The removed item's id stays retired after a reload.
+        assert!(loaded.add(Media::Placeholder).unwrap() > removed);
+    }
+
+    #[test]
+    fn loads_version_1_files() {
+        let mut catalog = load_text(
+            "version-1",
+            r#"{"version": 1, "items": [{"id": 3, "kind": "Book", "title": "Dune", "author": "Frank Herbert"}]}"#,
+        )
+        .unwrap();
+        let item = catalog.get("3".parse().unwrap()).unwrap();
+        assert_eq!(item.media.title(), Some("Dune"));
+        assert_eq!(item.metadata.year, None);
+        assert!(catalog.loans().is_empty());
+        assert_eq!(catalog.add(Media::Placeholder).unwrap().to_string(), "4");
+    }
+
+    #[test]
+    fn rejects_inconsistent_files() {
+        let result = load_text(
+            "duplicate-id",
+            r#"{"version": 3, "items": [{"id": 1, "kind": "Placeholder"}, {"id": 1, "kind": "Placeholder"}]}"#,
+        );
+        assert!(matches!(result, Err(StorageError::DuplicateId(id)) if id.to_string() == "1"));
+
+        let result = load_text(
+            "loan-of-missing-item",
+            r#"{"version": 3, "items": [], "loans": [{"item": 7, "borrower": "Ada", "checked_out": 0, "due": 1}]}"#,
+        );
+        assert!(matches!(result, Err(StorageError::InvalidLoan(id)) if id.to_string() == "7"));
+
+        let result = load_text(
+            "lent-twice",
+            r#"{"version": 3, "items": [{"id": 1, "kind": "Book", "title": "Dune", "author": "Frank Herbert"}],
+                "loans": [{"item": 1, "borrower": "Ada", "checked_out": 0, "due": 1},
+                          {"item": 1, "borrower": "Bob", "checked_out": 0, "due": 1}]}"#,
+        );
+        assert!(matches!(result, Err(StorageError::InvalidLoan(_))));
+
+        let result = load_text("future-version", r#"{"version": 99, "items": []}"#);
+        assert!(matches!(result, Err(StorageError::UnsupportedVersion(99))));
+
+        let result = load_text(
+            "invalid-item",
+            r#"{"version": 3, "items": [{"id": 1, "kind": "Placeholder"}, {"id": 2, "kind": "Movie", "title": "Alien", "director": ""}]}"#,
+        );
+        let error = result.err().unwrap();
+        assert!(matches!(&error, StorageError::InvalidItem(id, _) if id.to_string() == "2"));
+        assert_eq!(error.to_string(), "item 2: director cannot be empty");
+    }
+
+    #[test]
+    fn reports_csv_errors_by_line() {
+        let csv = "\
+kind,title,author,year
+book,Dune,Frank Herbert,1965
+book,,Nobody,
+book,Emma,Jane Austen,soon
+movie,Alien
+song,Yesterday,,
+book,Kindred,Octavia Butler,1979
+";
+        let mut catalog = Catalog::new();
+        let report = catalog.import_csv(csv.as_bytes()).unwrap();
+        assert_eq!(report.added.len(), 2);
+        let errors: Vec<(u64, &str)> = report
+            .errors
+            .iter()
+            .map(|e| (e.line, e.message.as_str()))
+            .collect();
+        assert_eq!(errors[0], (3, "title cannot be empty"));
+        assert_eq!(errors[1], (4, "invalid year \"soon\""));
+        assert_eq!(errors[2].0, 5);
+        assert_eq!(errors[3], (6, "unknown kind \"song\""));
+        assert_eq!(errors.len(), 4);
+
+        let result = catalog.import_csv("title\nDune\n".as_bytes());
+        assert!(matches!(result, Err(StorageError::MissingColumn("kind"))));
+    }
+}
@@ -1,42 +1,570 @@
-#![allow(dead_code)]
+mod cli;
 mod content;
-use content::catalog::Catalog;
-use content::media::Media;
+
+use cli::{Command, Due, Fields, Format, Options};
+use content::catalog::{Catalog, CatalogError, Id};
+use content::loans::Loan;
+use content::media::{Item, Kind, Media};
+use content::metadata::Timestamp;
+use content::search::Query;
+use serde::Serialize;
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+// Why a command failed, which decides the exit status.
+enum Failure {
+    NotFound(String),
+    Usage(String),
+    Storage(String),
+    Refused(String),
+    // Some import rows were rejected; the valid ones were still added.
+    Partial(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::NotFound(_) => 1,
+            Failure::Usage(_) => 2,
+            Failure::Storage(_) => 3,
+            Failure::Refused(_) => 4,
+            Failure::Partial(_) => 5,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            Failure::NotFound(message)
+            | Failure::Usage(message)
+            | Failure::Storage(message)
+            | Failure::Refused(message)
+            | Failure::Partial(message) => message,
+        }
+    }
+}
+
+// An item as printed in JSON: its id next to its fields.
+#[derive(Serialize)]
+struct Listed<'a> {
+    id: Id,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<u32>,
+    #[serde(flatten)]
+    item: &'a Item,
+}
+
+#[derive(Serialize)]
+struct ListedLoan<'a> {
+    #[serde(flatten)]
+    loan: &'a Loan,
+    overdue: bool,
+}
 
 fn main() {
-    let audiobook = Media::Audiobook {
-        title: "The Great Gatsby".to_string(),
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    let command = match options.command {
+        Some(command) if !options.help => command,
+        _ => {
+            println!("{}", cli::USAGE);
+            process::exit(if options.help { 0 } else { 2 });
+        }
     };
 
-    let book = Media::Book {
-        title: "The Great Gatsby".to_string(),
-        author: "F. Scott Fitzgerald".to_string(),
+    let path = options
+        .catalog
+        .or_else(|| env::var_os("MEDIA_CATALOG").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("catalog.json"));
+    if let Err(failure) = run(command, &path, options.format) {
+        eprintln!("{}", failure.message());
+        process::exit(failure.exit_code());
+    }
+}
+
+fn run(command: Command, path: &Path, format: Format) -> Result<(), Failure> {
+    // Commands that save hold the lock from loading to saving, so concurrent runs take turns
+    // instead of overwriting each other's changes. Reads need none as saves are atomic.
+    if let Command::Add { kind, fields } = command {
+        // Asked before locking, so a prompt left waiting holds up no one else.
+        let item = new_item(kind, fields)?;
+        let _lock = Catalog::lock(path).map_err(storage)?;
+        let mut catalog = open(path)?;
+        let id = catalog.add_item(item).map_err(refused)?;
+        save(&catalog, path)?;
+        report(format, "Added", id);
+        return Ok(());
+    }
+    let _lock = if command.changes_catalog() {
+        Some(Catalog::lock(path).map_err(storage)?)
+    } else {
+        None
     };
+    let mut catalog = open(path)?;
+    match command {
+        Command::Add { .. } => unreachable!("add is handled before the catalog is opened"),
+        Command::List { kinds } => {
+            let items: Vec<(Id, &Item)> = catalog
+                .iter()
+                .filter(|(_, item)| kinds.is_empty() || kinds.contains(&item.media.kind()))
+                .collect();
+            print_items(format, items.iter().map(|(id, item)| (*id, *item, None)));
+        }
+        Command::Show { id } => {
+            let item = catalog.get(id).map_err(not_found)?;
+            match format {
+                Format::Table => print_details(id, item, catalog.loans().current(id)),
+                Format::Json => print_json(&Listed {
+                    id,
+                    score: None,
+                    item,
+                }),
+            }
+        }
+        Command::Search {
+            words,
+            kinds,
+            limit,
+        } => {
+            let mut query = Query::new(&words.join(" "));
+            for kind in kinds {
+                query = query.kind(kind);
+            }
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+            let results = catalog.search(&query);
+            print_items(
+                format,
+                results
+                    .iter()
+                    .map(|result| (result.id, result.item, Some(result.score))),
+            );
+            if results.is_empty() {
+                return Err(Failure::NotFound(String::from("No items matched")));
+            }
+        }
+        Command::Edit { id, fields } => {
+            let mut edited = catalog.get(id).map_err(not_found)?.clone();
+            apply(&mut edited, fields)?;
+            catalog.update(id, |item| *item = edited).map_err(refused)?;
+            save(&catalog, path)?;
+            report(format, "Updated", id);
+        }
+        Command::Remove { id } => {
+            catalog.remove(id).map_err(refused)?;
+            save(&catalog, path)?;
+            report(format, "Removed", id);
+        }
+        Command::Import { file } => {
+            let result = if file == Path::new("-") {
+                catalog.import_csv(io::stdin().lock())
+            } else {
+                let reader =
+                    File::open(&file).map_err(|e| storage(format!("{}: {}", file.display(), e)))?;
+                catalog.import_csv(reader)
+            };
+            let imported = result.map_err(storage)?;
+            save(&catalog, path)?;
+            for error in &imported.errors {
+                eprintln!("{}: {}", file.display(), error);
+            }
+            println!("Imported {} items", imported.added.len());
+            if !imported.errors.is_empty() {
+                let message = format!("Skipped {} rows", imported.errors.len());
+                return Err(Failure::Partial(message));
+            }
+        }
+        Command::Export { file: None } => {
+            catalog.export_csv(io::stdout().lock()).map_err(storage)?
+        }
+        Command::Export { file: Some(file) } => catalog.save_csv(&file).map_err(storage)?,
+        Command::CheckOut { id, borrower, due } => {
+            let due = match due {
+                // Due by the last second of that day.
+                Due::On(date) => Some(date.end_of_day()),
+                Due::InDays(days) => Timestamp::now().plus_days(days),
+            }
+            .ok_or_else(|| Failure::Usage(String::from("the due date is too far away")))?;
+            let loan = catalog
+                .check_out(id, &borrower, due)
+                .map_err(refused)?
+                .clone();
+            save(&catalog, path)?;
+            match format {
+                Format::Table => {
+                    println!("Lent {} to {}, due {}", id, loan.borrower, loan.due.date())
+                }
+                Format::Json => print_json(&loan),
+            }
+        }
+        Command::CheckIn { id } => {
+            let loan = catalog.check_in(id).map_err(refused)?.clone();
+            save(&catalog, path)?;
+            match format {
+                Format::Table if loan.returned > Some(loan.due) => println!(
+                    "Returned {} from {}, late (was due {})",
+                    id,
+                    loan.borrower,
+                    loan.due.date()
+                ),
+                Format::Table => println!("Returned {} from {}", id, loan.borrower),
+                Format::Json => print_json(&loan),
+            }
+        }
+        Command::Loans { borrower, overdue } => {
+            let now = Timestamp::now();
+            let loans = catalog.loans();
+            let mut selected = match &borrower {
+                Some(borrower) => loans.history(borrower),
+                None if overdue => loans.overdue(now),
+                None => loans.open().collect(),
+            };
+            if overdue {
+                selected.retain(|loan| loan.is_overdue(now));
+            }
+            print_loans(format, &catalog, &selected, now);
+        }
+    }
+    Ok(())
+}
+
+// A missing file is an empty catalog, so the first `add` creates it.
+fn open(path: &Path) -> Result<Catalog, Failure> {
+    if path.exists() {
+        Catalog::load(path).map_err(storage)
+    } else {
+        Ok(Catalog::new())
+    }
+}
+
+fn save(catalog: &Catalog, path: &Path) -> Result<(), Failure> {
+    catalog.save(path).map_err(storage)
+}
+
+fn not_found(e: impl Display) -> Failure {
+    Failure::NotFound(e.to_string())
+}
+
+fn refused(e: CatalogError) -> Failure {
+    match e {
+        CatalogError::NotFound(_) => Failure::NotFound(e.to_string()),
+        CatalogError::Invalid(_) | CatalogError::NoBorrower | CatalogError::DueBeforeCheckout => {
+            Failure::Usage(e.to_string())
+        }
+        _ => Failure::Refused(e.to_string()),
+    }
+}
+
+fn storage(e: impl Display) -> Failure {
+    Failure::Storage(e.to_string())
+}
 
-    let movie = Media::Movie {
-        title: "The Great Gatsby".to_string(),
-        director: "Baz Luhrmann".to_string(),
+fn new_item(kind: Option<Kind>, mut fields: Fields) -> Result<Item, Failure> {
+    let kind = match kind {
+        Some(kind) => kind,
+        None => ask("Kind (book, movie, audiobook, podcast)", "KIND", |answer| {
+            answer.parse()
+        })?,
     };
+    let needs_title = matches!(kind, Kind::Book | Kind::Movie | Kind::Audiobook);
+    if needs_title && fields.title.is_none() {
+        fields.title = Some(ask("Title", "--title", non_empty)?);
+    }
+    if kind == Kind::Book && fields.author.is_none() {
+        fields.author = Some(ask("Author", "--author", non_empty)?);
+    }
+    if kind == Kind::Movie && fields.director.is_none() {
+        fields.director = Some(ask("Director", "--director", non_empty)?);
+    }
+    if kind == Kind::Podcast && fields.episode.is_none() {
+        fields.episode = Some(ask("Episode", "--episode", |answer| {
+            answer
+                .parse()
+                .map_err(|_| format!("invalid episode {:?}", answer))
+        })?);
+    }
 
-    let podcast = Media::Podcast { episode: 32 };
+    let mut item = Item::new(blank(kind));
+    apply(&mut item, fields)?;
+    // Checked before the catalog is locked; adding it checks again.
+    item.media.validate().map_err(Failure::Usage)?;
+    Ok(item)
+}
 
-    let placeholder = Media::Placeholder;
+fn blank(kind: Kind) -> Media {
+    match kind {
+        Kind::Book => Media::Book {
+            title: String::new(),
+            author: String::new(),
+        },
+        Kind::Movie => Media::Movie {
+            title: String::new(),
+            director: String::new(),
+        },
+        Kind::Audiobook => Media::Audiobook {
+            title: String::new(),
+            author: String::new(),
+            narrator: String::new(),
+        },
+        Kind::Podcast => Media::Podcast {
+            show: String::new(),
+            episode: 0,
+            title: String::new(),
+        },
+        Kind::Placeholder => Media::Placeholder,
+    }
+}
 
-    let mut catalog = Catalog::new();
+fn apply(item: &mut Item, fields: Fields) -> Result<(), Failure> {
+    let kind = item.media.kind();
+    let misplaced = |flag: &str| Failure::Usage(format!("{} does not apply to a {}", flag, kind));
+    if let Some(value) = fields.title {
+        match &mut item.media {
+            Media::Book { title, .. }
+            | Media::Movie { title, .. }
+            | Media::Audiobook { title, .. }
+            | Media::Podcast { title, .. } => *title = value,
+            Media::Placeholder => return Err(misplaced("--title")),
+        }
+    }
+    if let Some(value) = fields.author {
+        match &mut item.media {
+            Media::Book { author, .. } | Media::Audiobook { author, .. } => *author = value,
+            _ => return Err(misplaced("--author")),
+        }
+    }
+    if let Some(value) = fields.director {
+        match &mut item.media {
+            Media::Movie { director, .. } => *director = value,
+            _ => return Err(misplaced("--director")),
+        }
+    }
+    if let Some(value) = fields.narrator {
+        match &mut item.media {
+            Media::Audiobook { narrator, .. } => *narrator = value,
+            _ => return Err(misplaced("--narrator")),
+        }
+    }
+    if let Some(value) = fields.show {
+        match &mut item.media {
+            Media::Podcast { show, .. } => *show = value,
+            _ => return Err(misplaced("--show")),
+        }
+    }
+    if let Some(value) = fields.episode {
+        match &mut item.media {
+            Media::Podcast { episode, .. } => *episode = value,
+            _ => return Err(misplaced("--episode")),
+        }
+    }
+
+    let metadata = &mut item.metadata;
+    if let Some(year) = fields.year {
+        metadata.year = Some(year);
+    }
+    if let Some(genres) = fields.genres {
+        metadata.genres = genres;
+    }
+    if let Some(tags) = fields.tags {
+        metadata.tags = tags;
+    }
+    if let Some(rating) = fields.rating {
+        metadata.rating = Some(rating);
+    }
+    if let Some(length) = fields.length {
+        metadata.length = Some(length);
+    }
+    Ok(())
+}
+
+fn non_empty(answer: &str) -> Result<String, String> {
+    if answer.is_empty() {
+        Err(String::from("a value is required"))
+    } else {
+        Ok(answer.to_string())
+    }
+}
+
+// Asks on the terminal until `parse` accepts the answer. Without a terminal, e.g. in a
+// script, the missing value is an error instead.
+fn ask<T>(
+    label: &str,
+    flag: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T, Failure> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Err(Failure::Usage(format!("missing {}", flag)));
+    }
+    loop {
+        print!("{}: ", label);
+        io::stdout().flush().map_err(storage)?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer).map_err(storage)? == 0 {
+            return Err(Failure::Usage(format!("missing {}", flag)));
+        }
+        match parse(answer.trim()) {
+            Ok(value) => return Ok(value),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+fn report(format: Format, action: &str, id: Id) {
+    match format {
+        Format::Table => println!("{} {}", action, id),
+        Format::Json => print_json(&serde_json::json!({ "id": id })),
+    }
+}
+
+fn print_json(value: &impl Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+fn print_items<'a>(format: Format, items: impl Iterator<Item = (Id, &'a Item, Option<u32>)>) {
+    let items: Vec<Listed> = items
+        .map(|(id, item, score)| Listed { id, score, item })
+        .collect();
+    if format == Format::Json {
+        print_json(&items);
+        return;
+    }
+    if items.is_empty() {
+        return;
+    }
+
+    let mut rows = vec![["ID", "DESCRIPTION"].map(String::from)];
+    for listed in &items {
+        rows.push([
+            listed.id.to_string(),
+            truncate(&listed.item.description(), 100),
+        ]);
+    }
 
-    catalog.add(audiobook);
-    catalog.add(book);
-    catalog.add(movie);
-    catalog.add(podcast);
-    catalog.add(placeholder);
+    print_table(&rows);
+}
 
-    let item = catalog.get_by_index(2);
-    match item {
-        Some(media) => {
-            println!("Found item: {:#?}", media);
+// Left-aligned columns as wide as their widest cell; the first row is the header.
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
-        None => {
-            println!("Item not found");
+    }
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+fn print_details(id: Id, item: &Item, loan: Option<&Loan>) {
+    let metadata = &item.metadata;
+    println!("{}", item.description());
+    println!("  id:       {}", id);
+    if !metadata.tags.is_empty() {
+        println!("  tags:     {}", metadata.tags.join(", "));
+    }
+    println!("  added:    {}", metadata.added);
+    println!("  updated:  {}", metadata.updated);
+    if let Some(loan) = loan {
+        println!("  on loan:  to {}, due {}", loan.borrower, loan.due.date());
+    }
+}
+
+fn print_loans(format: Format, catalog: &Catalog, loans: &[&Loan], now: Timestamp) {
+    if format == Format::Json {
+        let loans: Vec<ListedLoan> = loans
+            .iter()
+            .map(|loan| ListedLoan {
+                loan,
+                overdue: loan.is_overdue(now),
+            })
+            .collect();
+        print_json(&loans);
+        return;
+    }
+    if loans.is_empty() {
+        return;
+    }
+
+    let mut rows = vec![["ITEM", "TITLE", "BORROWER", "OUT", "DUE", "RETURNED"].map(String::from)];
+    for loan in loans {
+        // Past loans may be of items removed since.
+        let title = catalog
+            .get(loan.item)
+            .ok()
+            .and_then(|item| item.media.title())
+            .unwrap_or("");
+        let due = if loan.is_overdue(now) {
+            format!("{} (overdue)", loan.due.date())
+        } else {
+            loan.due.date()
+        };
+        rows.push([
+            loan.item.to_string(),
+            truncate(title, 40),
+            truncate(&loan.borrower, 30),
+            loan.checked_out.date(),
+            due,
+            loan.returned
+                .map(|returned| returned.date())
+                .unwrap_or_default(),
+        ]);
+    }
+    print_table(&rows);
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let kept: String = text.chars().take(max - 1).collect();
+        format!("{}…", kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_tell_failures_apart() {
+        let failures = [
+            (Failure::NotFound(String::new()), 1),
+            (Failure::Usage(String::new()), 2),
+            (Failure::Storage(String::new()), 3),
+            (Failure::Refused(String::new()), 4),
+            (Failure::Partial(String::new()), 5),
+        ];
+        for (failure, code) in failures {
+            assert_eq!(failure.exit_code(), code, "{}", failure.message());
         }
     }
+
+    #[test]
+    fn maps_catalog_errors_to_failures() {
+        let id: Id = "7".parse().unwrap();
+        assert_eq!(refused(CatalogError::NotFound(id)).exit_code(), 1);
+        assert_eq!(refused(CatalogError::NoBorrower).exit_code(), 2);
+        assert_eq!(refused(CatalogError::NotOnLoan(id)).exit_code(), 4);
+        assert_eq!(storage("disk full").exit_code(), 3);
+    }
 }